@@ -1,69 +1,253 @@
 // src/app.rs
 
+use crate::confirm::{ConfirmAction, Confirmation};
+use crate::diff_view::{DiffLayout, DiffRows};
+use crate::diff_worker::{
+    DiffCache, DiffKey, DiffResponse, DiffTarget, DiffWorker, MergeView, SharedDiff,
+};
 use crate::git;
-use crate::types::{commit_info::CommitInfo, status_info::StatusInfo};
-use crossterm::event::{self, KeyCode, KeyEvent};
-use git2::Repository;
+use crate::log_loader::{LogLoader, LogPage};
+use crate::notifications::{Notifications, Severity};
+use crate::status_tree::{self, StatusRow};
+use crate::types::commit_info::CommitInfo;
+use crate::types::diff_info::{DiffInfo, FileDiff};
+use crate::types::diff_settings::DiffSettings;
+use crate::types::repo_summary::{BranchStatus, RepoSummary};
+use crate::types::status_info::{StatusInfo, StatusSide};
+use crate::watcher::{RepoChange, RepoWatcher};
+use crossterm::event::{self, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use git2::{Repository, RepositoryState};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use tui::text::Spans;
+use tui::layout::Rect;
 use tui::widgets::ListState;
 use tui_input::Input;
 use tui_input::backend::crossterm::EventHandler;
 
+/// Rows or diff lines moved per scroll wheel notch.
+const WHEEL_STEP: usize = 3;
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ActivePanel {
     Commits,
     Status,
+    Diff,
+}
+
+enum Jump {
+    Hunk,
+    File,
 }
 
 pub enum AppMode {
     Normal,
     CommitInput,
+    TagInput,
+    RevspecInput,
+    RepoSwitcher,
+    MessageLog,
+    Confirm(Confirmation),
     Pushing(String),
 }
 
+/// What the diff panel shows for a file picked from a commit's file list.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FileView {
+    Patch,
+    Blame,
+    History,
+}
+
+/// The files changed by one commit, listed in place of the log.
+pub struct CommitFiles {
+    pub commit: git2::Oid,
+    /// The commit's full diff, which the list is read from; `None` until the
+    /// diff worker delivers it.
+    pub diff: Option<SharedDiff>,
+    pub list_state: ListState,
+    pub list_offset: usize,
+    pub view: FileView,
+}
+
+impl CommitFiles {
+    pub fn files(&self) -> &[FileDiff] {
+        self.diff.as_ref().map_or(&[], |diff| &diff.files)
+    }
+
+    pub fn selected_file(&self) -> Option<&FileDiff> {
+        self.files().get(self.list_state.selected()?)
+    }
+}
+
+/// Pages from a restarted log walk are collected here and swapped into
+/// `App.commits` once the previously selected commit turns up again.
+struct LogRestart {
+    selected_id: Option<String>,
+    selected_index: usize,
+    commits: Vec<CommitInfo>,
+}
+
 pub struct App {
     pub repo: Repository,
+    /// Root directories of every repository TermiGit was started with.
+    pub workspace: Vec<PathBuf>,
+    /// Filled in when the repository switcher opens.
+    pub repo_summaries: Vec<RepoSummary>,
+    /// Summaries read in the background since the switcher was opened, by
+    /// their index in `workspace`.
+    pub summary_receiver: Option<mpsc::UnboundedReceiver<(usize, RepoSummary)>>,
+    pub repo_switcher_state: ListState,
+    /// HEAD and the operation in progress, for the status bar.
+    pub branch_status: BranchStatus,
+    pub repo_state: RepositoryState,
+    /// Results and errors of operations, shown as toasts and kept for the
+    /// message log.
+    pub notifications: Notifications,
+    pub message_log_state: ListState,
     pub should_quit: bool,
     pub active_panel: ActivePanel,
     pub mode: AppMode,
     pub commits: Vec<CommitInfo>,
+    pub log_loader: LogLoader,
+    log_restart: Option<LogRestart>,
+    /// Set while the Commits panel lists the files of one commit.
+    pub commit_files: Option<CommitFiles>,
+    /// While set, the log selection is diffed against this commit.
+    pub marked_commit: Option<git2::Oid>,
+    /// What a selected merge commit is diffed against; reset on every move.
+    pub merge_view: MergeView,
+    /// A diff asked for explicitly, such as a revspec or a commit against the
+    /// working tree, with its title; shown until the selection moves.
+    pub pinned_diff: Option<(String, DiffTarget)>,
     pub status_files: Vec<StatusInfo>,
+    /// Status entries marked for a bulk operation.
+    pub status_marks: HashSet<(String, StatusSide)>,
+    /// What the Status panel shows; `status_list_state` selects one of these.
+    pub status_rows: Vec<StatusRow>,
+    /// Group the Status panel by directory instead of listing paths.
+    pub status_tree: bool,
+    pub collapsed_dirs: HashSet<(StatusSide, String)>,
     pub commit_list_state: ListState,
     pub status_list_state: ListState,
-    pub diff_text: Vec<Spans<'static>>,
+    /// First visible row of each list; the UI only builds items from here on.
+    pub commit_list_offset: usize,
+    pub status_list_offset: usize,
+    pub diff_text: SharedDiff,
+    pub diff_layout: DiffLayout,
+    pub diff_settings: DiffSettings,
+    /// `diff_text` laid out in `diff_layout`; scrolling counts these rows.
+    pub diff_rows: DiffRows,
+    /// The list panel whose selection the diff panel shows (never `Diff`).
+    pub diff_source: ActivePanel,
+    /// A diff has been requested from `diff_worker` and not delivered yet.
+    /// Not set while reloading the diff already shown, which stays up.
+    pub diff_loading: bool,
+    /// What `diff_text` shows, so that reloading it keeps the scroll position.
+    diff_shown: Option<DiffTarget>,
+    /// First visible diff line and column, and whether long lines soft-wrap.
+    pub diff_scroll: usize,
+    pub diff_hscroll: u16,
+    pub diff_wrap: bool,
+    /// Rows available to the diff the last time it was drawn, for paging.
+    pub diff_view_height: usize,
+    /// Where each panel was last drawn, for mouse hit-testing.
+    pub commits_area: Rect,
+    pub status_area: Rect,
+    pub diff_area: Rect,
+    /// Time and position of the last left click, to detect double clicks.
+    last_click: Option<(Instant, u16, u16)>,
+    pub diff_worker: DiffWorker,
+    diff_cache: DiffCache,
     pub commit_input: Input,
-    pub push_feedback_sender: mpsc::Sender<String>,
-    pub push_feedback_receiver: mpsc::Receiver<String>,
+    pub tag_input: Input,
+    pub revspec_input: Input,
+    pub push_feedback_sender: mpsc::Sender<Result<String, String>>,
+    pub push_feedback_receiver: mpsc::Receiver<Result<String, String>>,
+    pub watcher: Option<RepoWatcher>,
 }
 
 impl App {
-    pub fn new() -> Result<Self, git2::Error> {
-        let repo = Repository::open(".").expect("Couldn't open repository in current dir");
-        let commits = git::fetch_log(&repo)?;
+    pub fn new(repo: Repository) -> Result<Self, git2::Error> {
+        let repo_path = repo.path().to_path_buf();
+        let log_loader = LogLoader::start(repo_path.clone());
         let status_files = git::fetch_status(&repo)?;
         let (tx, rx) = mpsc::channel(1);
+        // Without a watcher (e.g. inotify limits reached) we still refresh after our own actions.
+        let watcher = RepoWatcher::new(repo.workdir(), repo.path());
 
+        let workspace = vec![repo.workdir().unwrap_or(repo.path()).to_path_buf()];
         let mut app = Self {
             repo,
+            workspace,
+            repo_summaries: Vec::new(),
+            summary_receiver: None,
+            repo_switcher_state: ListState::default(),
+            branch_status: BranchStatus::default(),
+            repo_state: RepositoryState::Clean,
+            notifications: Notifications::default(),
+            message_log_state: ListState::default(),
             should_quit: false,
             active_panel: ActivePanel::Commits,
             mode: AppMode::Normal,
-            commits,
+            commits: Vec::new(),
+            log_loader,
+            log_restart: None,
+            commit_files: None,
+            marked_commit: None,
+            merge_view: MergeView::Parent(0),
+            pinned_diff: None,
             status_files,
+            status_marks: HashSet::new(),
+            status_rows: Vec::new(),
+            status_tree: false,
+            collapsed_dirs: HashSet::new(),
             commit_list_state: ListState::default(),
             status_list_state: ListState::default(),
-            diff_text: Vec::new(),
+            commit_list_offset: 0,
+            status_list_offset: 0,
+            diff_text: SharedDiff::default(),
+            diff_layout: DiffLayout::Unified,
+            diff_settings: DiffSettings::default(),
+            diff_rows: DiffRows::default(),
+            diff_source: ActivePanel::Commits,
+            diff_loading: false,
+            diff_shown: None,
+            diff_scroll: 0,
+            diff_hscroll: 0,
+            diff_wrap: false,
+            diff_view_height: 0,
+            commits_area: Rect::default(),
+            status_area: Rect::default(),
+            diff_area: Rect::default(),
+            last_click: None,
+            diff_worker: DiffWorker::start(repo_path.clone()),
+            diff_cache: DiffCache::default(),
             commit_input: Input::default(),
+            tag_input: Input::default(),
+            revspec_input: Input::default(),
             push_feedback_sender: tx,
             push_feedback_receiver: rx,
+            watcher: None,
         };
-
-        if !app.commits.is_empty() {
-            app.commit_list_state.select(Some(0));
-        }
-        if !app.status_files.is_empty() {
-            app.status_list_state.select(Some(0));
+        match watcher {
+            Ok(watcher) => app.watcher = Some(watcher),
+            Err(e) => app.notify(
+                Severity::Warning,
+                format!("Not watching the repository for changes: {e}"),
+            ),
         }
+
+        app.rebuild_status_rows(None);
+        let first_file = app
+            .status_rows
+            .iter()
+            .position(|row| matches!(row, StatusRow::File { .. }));
+        app.status_list_state.select(first_file.or(Some(0)));
+        app.refresh_head();
         app.update_diff();
 
         Ok(app)
@@ -73,6 +257,11 @@ impl App {
         match self.mode {
             AppMode::Normal => self.handle_normal_mode_keys(key),
             AppMode::CommitInput => self.handle_commit_input_keys(key),
+            AppMode::TagInput => self.handle_tag_input_keys(key),
+            AppMode::RevspecInput => self.handle_revspec_input_keys(key),
+            AppMode::RepoSwitcher => self.handle_repo_switcher_keys(key),
+            AppMode::MessageLog => self.handle_message_log_keys(key),
+            AppMode::Confirm(_) => self.handle_confirm_keys(key),
             AppMode::Pushing(_) => {
                 if let KeyCode::Enter | KeyCode::Esc = key.code {
                     self.mode = AppMode::Normal;
@@ -82,32 +271,536 @@ impl App {
     }
 
     fn handle_normal_mode_keys(&mut self, key: KeyEvent) {
+        if self.active_panel == ActivePanel::Diff && self.handle_diff_keys(key) {
+            return;
+        }
+        if self.active_panel == ActivePanel::Commits
+            && self.commit_files.is_some()
+            && self.handle_commit_files_keys(key)
+        {
+            return;
+        }
         match key.code {
             KeyCode::Char('q') => self.should_quit = true,
             KeyCode::Tab => {
-                self.active_panel = match self.active_panel {
+                let next = match self.active_panel {
                     ActivePanel::Commits => ActivePanel::Status,
+                    ActivePanel::Status => ActivePanel::Diff,
+                    ActivePanel::Diff => ActivePanel::Commits,
+                };
+                self.focus_panel(next);
+            }
+            KeyCode::BackTab => {
+                let previous = match self.active_panel {
+                    ActivePanel::Commits => ActivePanel::Diff,
                     ActivePanel::Status => ActivePanel::Commits,
+                    ActivePanel::Diff => ActivePanel::Status,
                 };
-                self.update_diff();
+                self.focus_panel(previous);
             }
             KeyCode::Down => self.select_next(),
             KeyCode::Up => self.select_previous(),
-            KeyCode::Char('c') => {
-                if !self.status_files.is_empty() {
-                    self.mode = AppMode::CommitInput;
-                }
+            KeyCode::Char('c') if !self.status_files.is_empty() => {
+                self.mode = AppMode::CommitInput;
+            }
+            KeyCode::Char('t')
+                if matches!(self.active_panel, ActivePanel::Commits)
+                    && self.commit_list_state.selected().is_some() =>
+            {
+                self.mode = AppMode::TagInput;
             }
             KeyCode::Char(' ') => {
                 if let ActivePanel::Status = self.active_panel {
                     self.toggle_stage_selection();
                 }
             }
+            KeyCode::Char('m') if matches!(self.active_panel, ActivePanel::Status) => {
+                self.toggle_status_mark();
+            }
+            KeyCode::Char('a') if matches!(self.active_panel, ActivePanel::Status) => {
+                let stage = self.status_paths(StatusSide::Unstaged);
+                self.apply_staging(stage, Vec::new());
+            }
+            KeyCode::Char('u') if matches!(self.active_panel, ActivePanel::Status) => {
+                let unstage = self.status_paths(StatusSide::Staged);
+                self.apply_staging(Vec::new(), unstage);
+            }
+            KeyCode::Char('i') if matches!(self.active_panel, ActivePanel::Status) => {
+                self.ignore_targets();
+            }
+            KeyCode::Char('t') if matches!(self.active_panel, ActivePanel::Status) => {
+                self.toggle_status_tree();
+            }
+            KeyCode::Enter if matches!(self.active_panel, ActivePanel::Status) => {
+                self.fold_status_dir(None);
+            }
+            KeyCode::Left if matches!(self.active_panel, ActivePanel::Status) => {
+                self.fold_status_dir(Some(false));
+            }
+            KeyCode::Right if matches!(self.active_panel, ActivePanel::Status) => {
+                self.fold_status_dir(Some(true));
+            }
+            KeyCode::Enter if matches!(self.active_panel, ActivePanel::Commits) => {
+                self.open_commit_files();
+            }
+            KeyCode::Char('m') if matches!(self.active_panel, ActivePanel::Commits) => {
+                self.toggle_mark();
+            }
+            KeyCode::Char('p') if matches!(self.active_panel, ActivePanel::Commits) => {
+                self.cycle_merge_view();
+            }
+            KeyCode::Char('d') if matches!(self.active_panel, ActivePanel::Commits) => {
+                self.mode = AppMode::RevspecInput;
+            }
+            KeyCode::Char('w') if matches!(self.active_panel, ActivePanel::Commits) => {
+                self.pin_workspace_diff(false);
+            }
+            KeyCode::Char('i') if matches!(self.active_panel, ActivePanel::Commits) => {
+                self.pin_workspace_diff(true);
+            }
+            KeyCode::Char('x') if matches!(self.active_panel, ActivePanel::Status) => {
+                self.confirm_discard(false);
+            }
+            KeyCode::Char('X') if matches!(self.active_panel, ActivePanel::Status) => {
+                self.confirm_discard(true);
+            }
             KeyCode::Char('P') => self.initiate_push(),
+            KeyCode::Char('R') => self.open_repo_switcher(),
+            KeyCode::Char('L') => self.open_message_log(),
+            _ => {}
+        }
+    }
+
+    pub fn notify(&mut self, severity: Severity, message: String) {
+        self.notifications.push(severity, message);
+    }
+
+    fn open_message_log(&mut self) {
+        self.notifications.dismiss_toasts();
+        let first = (self.notifications.len() > 0).then_some(0);
+        self.message_log_state.select(first);
+        self.mode = AppMode::MessageLog;
+    }
+
+    fn handle_message_log_keys(&mut self, key: KeyEvent) {
+        let len = self.notifications.len();
+        let selected = self.message_log_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Down | KeyCode::Char('j') if len > 0 => {
+                self.message_log_state
+                    .select(Some((selected + 1).min(len - 1)));
+            }
+            KeyCode::Up | KeyCode::Char('k') if len > 0 => {
+                self.message_log_state
+                    .select(Some(selected.saturating_sub(1)));
+            }
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q' | 'L') => {
+                self.mode = AppMode::Normal;
+            }
+            _ => {}
+        }
+    }
+
+    /// Lists the workspace right away and reads each repository's state on
+    /// a background thread, since statuses of large trees take a while.
+    fn open_repo_switcher(&mut self) {
+        self.repo_summaries = self
+            .workspace
+            .iter()
+            .map(|path| RepoSummary::pending(path))
+            .collect();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let workspace = self.workspace.clone();
+        thread::spawn(move || {
+            for (index, path) in workspace.into_iter().enumerate() {
+                let summary = git::repo_summary(&path).unwrap_or_else(|_| RepoSummary {
+                    name: path.display().to_string(),
+                    loaded: true,
+                    ..RepoSummary::pending(&path)
+                });
+                // Stop once the switcher has been reopened or the app is gone.
+                if tx.send((index, summary)).is_err() {
+                    return;
+                }
+            }
+        });
+        self.summary_receiver = Some(rx);
+        let current = self.workspace.iter().position(|path| path == self.root());
+        self.repo_switcher_state.select(current.or(Some(0)));
+        self.mode = AppMode::RepoSwitcher;
+    }
+
+    pub fn handle_repo_summary(&mut self, index: usize, summary: RepoSummary) {
+        if let Some(row) = self.repo_summaries.get_mut(index) {
+            *row = summary;
+        }
+    }
+
+    fn handle_repo_switcher_keys(&mut self, key: KeyEvent) {
+        let len = self.repo_summaries.len();
+        let selected = self.repo_switcher_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Down | KeyCode::Char('j') if len > 0 => {
+                self.repo_switcher_state.select(Some((selected + 1) % len));
+            }
+            KeyCode::Up | KeyCode::Char('k') if len > 0 => {
+                self.repo_switcher_state
+                    .select(Some((selected + len - 1) % len));
+            }
+            KeyCode::Enter => {
+                self.mode = AppMode::Normal;
+                if let Some(path) = self.workspace.get(selected).cloned()
+                    && path != self.root()
+                {
+                    self.switch_repo(&path);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = AppMode::Normal,
+            _ => {}
+        }
+    }
+
+    fn root(&self) -> &Path {
+        self.repo.workdir().unwrap_or(self.repo.path())
+    }
+
+    /// Replaces all per-repository state, including the log loader, diff
+    /// worker and watcher, with that of the repository at `path`. View
+    /// preferences carry over.
+    fn switch_repo(&mut self, path: &Path) {
+        let next = match Repository::open(path).and_then(App::new) {
+            Ok(next) => next,
+            Err(e) => {
+                self.notify(
+                    Severity::Error,
+                    format!("Could not open {}: {e}", path.display()),
+                );
+                return;
+            }
+        };
+        let previous = std::mem::replace(self, next);
+        self.workspace = previous.workspace;
+        // A push still running in the background reports here.
+        self.push_feedback_sender = previous.push_feedback_sender;
+        self.push_feedback_receiver = previous.push_feedback_receiver;
+        let opened = std::mem::replace(&mut self.notifications, previous.notifications);
+        self.notifications.append(opened);
+        self.diff_layout = previous.diff_layout;
+        self.diff_settings = previous.diff_settings;
+        self.diff_wrap = previous.diff_wrap;
+        self.commits_area = previous.commits_area;
+        self.status_area = previous.status_area;
+        self.diff_area = previous.diff_area;
+        self.diff_view_height = previous.diff_view_height;
+        self.update_diff();
+    }
+
+    /// Keys for the commit file list. Returns false for keys it leaves to the
+    /// normal handler.
+    fn handle_commit_files_keys(&mut self, key: KeyEvent) -> bool {
+        let Some(files) = &mut self.commit_files else {
+            return false;
+        };
+        let toggle = |view: FileView, other: FileView| {
+            if view == other {
+                FileView::Patch
+            } else {
+                other
+            }
+        };
+        match key.code {
+            KeyCode::Esc => self.commit_files = None,
+            KeyCode::Enter if files.selected_file().is_some() => {
+                self.focus_panel(ActivePanel::Diff);
+                return true;
+            }
+            KeyCode::Char('b') if files.selected_file().is_some() => {
+                files.view = toggle(files.view, FileView::Blame);
+            }
+            KeyCode::Char('h') if files.selected_file().is_some() => {
+                files.view = toggle(files.view, FileView::History);
+            }
+            _ => return false,
+        }
+        self.update_diff();
+        true
+    }
+
+    /// Runs `confirmation`'s action straight away if the user turned its
+    /// prompt off, otherwise asks first.
+    fn request_confirmation(&mut self, confirmation: Confirmation) {
+        if confirmation.action.needs_confirmation(&self.repo) {
+            self.mode = AppMode::Confirm(confirmation);
+        } else {
+            self.run_confirmed(confirmation.action);
+        }
+    }
+
+    fn handle_confirm_keys(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('y') => {
+                if let AppMode::Confirm(confirmation) =
+                    std::mem::replace(&mut self.mode, AppMode::Normal)
+                {
+                    self.run_confirmed(confirmation.action);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('n' | 'q') => self.mode = AppMode::Normal,
             _ => {}
         }
     }
 
+    fn run_confirmed(&mut self, action: ConfirmAction) {
+        match action {
+            ConfirmAction::Discard { paths, from_head } => {
+                self.status_marks.clear();
+                match git::discard_changes(&self.repo, &paths, from_head) {
+                    Ok(()) => {
+                        let message = match paths.as_slice() {
+                            [path] => format!("Discarded changes to {path}"),
+                            _ => format!("Discarded changes to {} files", paths.len()),
+                        };
+                        self.notify(Severity::Success, message);
+                    }
+                    Err(e) => self.notify(Severity::Error, format!("Discard failed: {e}")),
+                }
+                // Some files may have been discarded before a failure.
+                self.refresh_status();
+                self.update_diff();
+            }
+        }
+    }
+
+    /// Asks to discard the selected file's unstaged changes, or with
+    /// `from_head` all of its changes.
+    fn confirm_discard(&mut self, from_head: bool) {
+        let unstaged = git2::Status::WT_NEW
+            | git2::Status::WT_MODIFIED
+            | git2::Status::WT_DELETED
+            | git2::Status::WT_TYPECHANGE
+            | git2::Status::WT_RENAMED;
+        let mut targets: Vec<&StatusInfo> = self
+            .status_targets()
+            .into_iter()
+            .filter(|s| from_head || s.status.intersects(unstaged))
+            .collect();
+        // A file listed on both sides is discarded once.
+        let mut seen = HashSet::new();
+        targets.retain(|s| seen.insert(s.path.as_str()));
+        if targets.is_empty() {
+            if self.status_list_state.selected().is_some() {
+                self.notify(Severity::Info, "No unstaged changes to discard".to_string());
+            }
+            return;
+        }
+        // Untracked files, and with `from_head` newly added ones, are deleted.
+        let (deleted, restored): (Vec<&StatusInfo>, Vec<&StatusInfo>) =
+            targets.iter().partition(|s| {
+                (s.status.is_index_new() && from_head)
+                    || (s.status.is_wt_new() && !s.status.is_index_new())
+            });
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let title = if from_head {
+            "Discard all changes"
+        } else {
+            "Discard unstaged changes"
+        };
+        let paths = targets.iter().map(|s| s.path.clone()).collect();
+        let mut confirmation = Confirmation::new(
+            ConfirmAction::Discard { paths, from_head },
+            title.to_string(),
+        );
+        if !restored.is_empty() {
+            confirmation.list(
+                format!(
+                    "Changes to {} file{} will be lost:",
+                    restored.len(),
+                    plural(restored.len())
+                ),
+                restored.iter().map(|s| s.path.clone()),
+            );
+        }
+        if !deleted.is_empty() {
+            confirmation.list(
+                format!(
+                    "{} file{} will be deleted:",
+                    deleted.len(),
+                    plural(deleted.len())
+                ),
+                deleted.iter().map(|s| s.path.clone()),
+            );
+        }
+        self.request_confirmation(confirmation);
+    }
+
+    fn selected_commit_id(&self) -> Option<git2::Oid> {
+        let commit = self.commits.get(self.commit_list_state.selected()?)?;
+        git2::Oid::from_str(&commit.id).ok()
+    }
+
+    /// Marks the selected commit, or clears the mark if it is already marked.
+    fn toggle_mark(&mut self) {
+        let selected = self.selected_commit_id();
+        self.marked_commit = if self.marked_commit == selected {
+            None
+        } else {
+            selected
+        };
+        self.update_diff();
+    }
+
+    /// Diffs the selected commit against the working tree, or the index.
+    fn pin_workspace_diff(&mut self, index_only: bool) {
+        let Some(commit) = self.selected_commit_id() else {
+            return;
+        };
+        let side = if index_only { "index" } else { "working tree" };
+        let title = format!("{:.7}..{side}", commit.to_string());
+        self.pinned_diff = Some((title, DiffTarget::Workspace { commit, index_only }));
+        self.update_diff();
+    }
+
+    /// Steps a selected merge through each of its parents and then the
+    /// combined view.
+    fn cycle_merge_view(&mut self) {
+        let parents = self
+            .selected_commit_id()
+            .and_then(|id| self.repo.find_commit(id).ok())
+            .map_or(0, |commit| commit.parent_count());
+        if parents < 2 {
+            return;
+        }
+        self.merge_view = match self.merge_view {
+            MergeView::Parent(parent) if parent + 1 < parents => MergeView::Parent(parent + 1),
+            MergeView::Parent(_) => MergeView::Combined,
+            MergeView::Combined => MergeView::Parent(0),
+        };
+        self.update_diff();
+    }
+
+    fn open_commit_files(&mut self) {
+        let Some(commit) = self.selected_commit_id() else {
+            return;
+        };
+        self.commit_files = Some(CommitFiles {
+            commit,
+            diff: None,
+            list_state: ListState::default(),
+            list_offset: 0,
+            view: FileView::Patch,
+        });
+        self.update_diff();
+    }
+
+    /// Scrolling keys for the focused diff panel. Returns false for keys it
+    /// leaves to the normal handler.
+    fn handle_diff_keys(&mut self, key: KeyEvent) -> bool {
+        let page = self.diff_view_height.max(1);
+        match key.code {
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_diff_to(self.diff_scroll + 1),
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.scroll_diff_to(self.diff_scroll.saturating_sub(1))
+            }
+            KeyCode::PageDown => self.scroll_diff_to(self.diff_scroll + page),
+            KeyCode::PageUp => self.scroll_diff_to(self.diff_scroll.saturating_sub(page)),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll_diff_to(0),
+            KeyCode::End | KeyCode::Char('G') => self.scroll_diff_to(usize::MAX),
+            KeyCode::Char('n') => self.jump_diff(Jump::Hunk, true),
+            KeyCode::Char('N') => self.jump_diff(Jump::Hunk, false),
+            KeyCode::Char(']') => self.jump_diff(Jump::File, true),
+            KeyCode::Char('[') => self.jump_diff(Jump::File, false),
+            KeyCode::Right | KeyCode::Char('l') if !self.wraps_diff() => {
+                self.diff_hscroll = self.diff_hscroll.saturating_add(4);
+            }
+            KeyCode::Left | KeyCode::Char('h') if !self.wraps_diff() => {
+                self.diff_hscroll = self.diff_hscroll.saturating_sub(4);
+            }
+            KeyCode::Char('w') => {
+                self.diff_wrap = !self.diff_wrap;
+                self.diff_hscroll = 0;
+            }
+            KeyCode::Char('s') => self.toggle_diff_layout(),
+            KeyCode::Char('W') => {
+                self.diff_settings.whitespace = self.diff_settings.whitespace.next();
+                self.update_diff();
+            }
+            KeyCode::Char('a') => {
+                self.diff_settings.algorithm = self.diff_settings.algorithm.next();
+                self.update_diff();
+            }
+            KeyCode::Char('r') => {
+                self.diff_settings.find_renames = !self.diff_settings.find_renames;
+                self.update_diff();
+            }
+            KeyCode::Char('+') => {
+                self.diff_settings.context_lines = (self.diff_settings.context_lines + 1).min(99);
+                self.update_diff();
+            }
+            KeyCode::Char('-') if self.diff_settings.context_lines > 0 => {
+                self.diff_settings.context_lines -= 1;
+                self.update_diff();
+            }
+            KeyCode::Esc => self.focus_panel(self.diff_source),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Soft-wrap only applies to the unified layout.
+    pub fn wraps_diff(&self) -> bool {
+        self.diff_wrap && self.diff_layout == DiffLayout::Unified
+    }
+
+    fn toggle_diff_layout(&mut self) {
+        self.diff_layout = match self.diff_layout {
+            DiffLayout::Unified => DiffLayout::SideBySide,
+            DiffLayout::SideBySide => DiffLayout::Unified,
+        };
+        // Keep roughly the same place: the first hunk at or above the top row.
+        let hunk = self
+            .diff_rows
+            .hunk_starts
+            .iter()
+            .rposition(|&start| start <= self.diff_scroll);
+        self.diff_rows = DiffRows::new(&self.diff_text, self.diff_layout, self.diff_file_filter());
+        self.diff_scroll = hunk.map_or(0, |i| self.diff_rows.hunk_starts[i]);
+    }
+
+    fn scroll_diff_to(&mut self, line: usize) {
+        let last_page = self
+            .diff_rows
+            .len()
+            .saturating_sub(self.diff_view_height.max(1));
+        self.diff_scroll = line.min(last_page);
+    }
+
+    fn jump_diff(&mut self, jump: Jump, forward: bool) {
+        let starts = match jump {
+            Jump::Hunk => &self.diff_rows.hunk_starts,
+            Jump::File => &self.diff_rows.file_starts,
+        };
+        let target = if forward {
+            starts.iter().find(|&&start| start > self.diff_scroll)
+        } else {
+            starts.iter().rev().find(|&&start| start < self.diff_scroll)
+        };
+        if let Some(&line) = target {
+            self.diff_scroll = line;
+        }
+    }
+
+    fn focus_panel(&mut self, panel: ActivePanel) {
+        self.active_panel = panel;
+        if panel == ActivePanel::Diff {
+            return;
+        }
+        let was_pinned = self.pinned_diff.take().is_some();
+        if panel != self.diff_source || was_pinned {
+            self.diff_source = panel;
+            self.update_diff();
+        }
+    }
+
     fn handle_commit_input_keys(&mut self, key: KeyEvent) {
         match key.code {
             KeyCode::Enter => self.submit_commit(),
@@ -121,13 +814,93 @@ impl App {
         }
     }
 
+    fn handle_tag_input_keys(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.submit_tag(),
+            KeyCode::Esc => {
+                self.tag_input.reset();
+                self.mode = AppMode::Normal;
+            }
+            _ => {
+                self.tag_input.handle_event(&event::Event::Key(key));
+            }
+        }
+    }
+
+    fn handle_revspec_input_keys(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.submit_revspec(),
+            KeyCode::Esc => {
+                self.revspec_input.reset();
+                self.mode = AppMode::Normal;
+            }
+            _ => {
+                self.revspec_input.handle_event(&event::Event::Key(key));
+            }
+        }
+    }
+
+    fn submit_revspec(&mut self) {
+        let spec = self.revspec_input.value().trim().to_string();
+        if spec.is_empty() {
+            return;
+        }
+        let target = match git::resolve_revspec(&self.repo, &spec) {
+            Ok((Some(from), to)) => DiffTarget::Range { from, to },
+            Ok((None, commit)) => DiffTarget::Commit(commit),
+            Err(e) => {
+                self.notify(Severity::Error, format!("Bad revision '{spec}': {e}"));
+                return;
+            }
+        };
+        self.revspec_input.reset();
+        self.mode = AppMode::Normal;
+        self.pinned_diff = Some((spec, target));
+        self.active_panel = ActivePanel::Diff;
+        self.update_diff();
+    }
+
     fn submit_commit(&mut self) {
         let message = self.commit_input.value();
-        // CORRECTED: Collapsed the nested 'if' statement
-        if !message.is_empty() && git::create_commit(&self.repo, message).is_ok() {
-            self.commit_input.reset();
-            self.mode = AppMode::Normal;
-            self.refresh_all();
+        if message.is_empty() {
+            return;
+        }
+        let summary = message.lines().next().unwrap_or_default().to_string();
+        match git::create_commit(&self.repo, message) {
+            Ok(()) => {
+                self.commit_input.reset();
+                self.mode = AppMode::Normal;
+                self.notify(Severity::Success, format!("Committed \"{summary}\""));
+                self.refresh_all();
+            }
+            // The popup stays open so the message is not lost.
+            Err(e) => self.notify(Severity::Error, format!("Commit failed: {e}")),
+        }
+    }
+
+    fn submit_tag(&mut self) {
+        let value = self.tag_input.value().trim();
+        let (name, message) = value.split_once(' ').unwrap_or((value, value));
+        if name.is_empty() {
+            return;
+        }
+        let Some(commit) = self
+            .commit_list_state
+            .selected()
+            .and_then(|i| self.commits.get(i))
+        else {
+            return;
+        };
+        match git::create_tag(&self.repo, commit, name, message.trim()) {
+            Ok(()) => {
+                let message = format!("Tagged {} as {name}", &commit.id[..7]);
+                self.notify(Severity::Success, message);
+                self.tag_input.reset();
+                self.mode = AppMode::Normal;
+                self.diff_cache.invalidate_refs();
+                self.update_diff();
+            }
+            Err(e) => self.notify(Severity::Error, format!("Tag failed: {e}")),
         }
     }
 
@@ -139,109 +912,727 @@ impl App {
         tokio::task::spawn_blocking(move || {
             let result_msg = match Repository::open(repo_path) {
                 Ok(repo) => match git::push_to_remote(&repo) {
-                    Ok(_) => "Push successful!".to_string(),
+                    Ok(_) => Ok("Push successful!".to_string()),
                     // CORRECTED: Use modern f-string style formatting
-                    Err(e) => format!("Push failed: {e}"),
+                    Err(e) => Err(format!("Push failed: {e}")),
                 },
                 // CORRECTED: Use modern f-string style formatting
-                Err(e) => format!("Failed to open repo: {e}"),
+                Err(e) => Err(format!("Failed to open repo: {e}")),
             };
             let _ = sender.blocking_send(result_msg);
         });
     }
 
+    pub fn handle_push_feedback(&mut self, result: Result<String, String>) {
+        if let AppMode::Pushing(_) = self.mode {
+            self.mode = AppMode::Normal;
+        }
+        match result {
+            Ok(msg) => self.notify(Severity::Success, msg),
+            Err(msg) => self.notify(Severity::Error, msg),
+        }
+        self.refresh_head();
+        // The push may have moved remote-tracking branches.
+        self.diff_cache.invalidate_refs();
+        self.update_diff();
+    }
+
+    fn selected_status_row(&self) -> Option<&StatusRow> {
+        self.status_rows.get(self.status_list_state.selected()?)
+    }
+
+    /// The marked Status entries, or when none are marked those of the
+    /// selected row: one file, or all below a directory or heading.
+    fn status_targets(&self) -> Vec<&StatusInfo> {
+        if self.status_marks.is_empty() {
+            return self
+                .selected_status_row()
+                .map_or(&[][..], |row| &self.status_files[row.files()])
+                .iter()
+                .collect();
+        }
+        self.status_files
+            .iter()
+            .filter(|s| self.status_marks.contains(&(s.path.clone(), s.side)))
+            .collect()
+    }
+
+    fn status_paths(&self, side: StatusSide) -> Vec<String> {
+        self.status_files
+            .iter()
+            .filter(|s| s.side == side)
+            .map(|s| s.path.clone())
+            .collect()
+    }
+
+    /// Marks or unmarks the selected row's files and moves on to the next
+    /// row. A directory whose files are only partly marked gets all marked.
+    fn toggle_status_mark(&mut self) {
+        let Some(row) = self.selected_status_row() else {
+            return;
+        };
+        let keys: Vec<(String, StatusSide)> = self.status_files[row.files()]
+            .iter()
+            .map(|s| (s.path.clone(), s.side))
+            .collect();
+        if keys.iter().all(|key| self.status_marks.contains(key)) {
+            for key in &keys {
+                self.status_marks.remove(key);
+            }
+        } else {
+            self.status_marks.extend(keys);
+        }
+        self.select_next();
+    }
+
+    fn toggle_status_tree(&mut self) {
+        self.status_tree = !self.status_tree;
+        self.rebuild_status_rows(self.selected_status_key());
+        self.update_diff();
+    }
+
+    /// Collapses or expands the selected directory; `expand` of `None`
+    /// toggles it.
+    fn fold_status_dir(&mut self, expand: Option<bool>) {
+        let Some(StatusRow::Dir {
+            side,
+            path,
+            collapsed,
+            ..
+        }) = self.selected_status_row()
+        else {
+            return;
+        };
+        let key = (*side, path.clone());
+        if expand.unwrap_or(*collapsed) {
+            self.collapsed_dirs.remove(&key);
+        } else {
+            self.collapsed_dirs.insert(key);
+        }
+        self.rebuild_status_rows(self.selected_status_key());
+    }
+
+    /// Stages the unstaged targets and unstages the staged ones.
     fn toggle_stage_selection(&mut self) {
-        if let Some(selected) = self.status_list_state.selected() {
-            if let Some(item) = self.status_files.get(selected) {
-                if git::stage_toggle(&self.repo, &item.path).is_ok() {
-                    self.refresh_status();
-                    self.update_diff();
-                }
+        let (staged, unstaged): (Vec<&StatusInfo>, Vec<&StatusInfo>) = self
+            .status_targets()
+            .into_iter()
+            .partition(|s| s.side == StatusSide::Staged);
+        let stage = unstaged.iter().map(|s| s.path.clone()).collect();
+        let unstage = staged.iter().map(|s| s.path.clone()).collect();
+        self.apply_staging(stage, unstage);
+    }
+
+    fn apply_staging(&mut self, stage: Vec<String>, unstage: Vec<String>) {
+        if stage.is_empty() && unstage.is_empty() {
+            return;
+        }
+        let describe = |paths: &[String]| match paths {
+            [path] => path.clone(),
+            _ => format!("{} files", paths.len()),
+        };
+        let mut done = Vec::new();
+        if !stage.is_empty() {
+            done.push(format!("staged {}", describe(&stage)));
+        }
+        if !unstage.is_empty() {
+            done.push(format!("unstaged {}", describe(&unstage)));
+        }
+        let done = done.join(" and ");
+        match git::update_index(&self.repo, &stage, &unstage) {
+            Ok(()) => {
+                self.notify(Severity::Info, capitalize(&done));
+                self.status_marks.clear();
+                self.refresh_status();
+                self.update_diff();
             }
+            Err(e) => self.notify(Severity::Error, format!("Could not update the index: {e}")),
         }
     }
 
-    pub fn update_diff(&mut self) {
-        let diff_result = match self.active_panel {
+    /// Adds the untracked targets to `.gitignore`.
+    fn ignore_targets(&mut self) {
+        let paths: Vec<String> = self
+            .status_targets()
+            .into_iter()
+            .filter(|s| s.code() == '?')
+            .map(|s| s.path.clone())
+            .collect();
+        if paths.is_empty() {
+            if self.status_list_state.selected().is_some() {
+                self.notify(
+                    Severity::Info,
+                    "Only untracked files can be ignored".to_string(),
+                );
+            }
+            return;
+        }
+        match git::ignore_paths(&self.repo, &paths) {
+            Ok(()) => {
+                let message = match paths.as_slice() {
+                    [path] => format!("Ignored {path}"),
+                    _ => format!("Ignored {} files", paths.len()),
+                };
+                self.notify(Severity::Info, message);
+                self.status_marks.clear();
+                self.refresh_status();
+                self.update_diff();
+            }
+            Err(e) => self.notify(Severity::Error, e.message().to_string()),
+        }
+    }
+
+    fn diff_key(&self) -> Option<DiffKey> {
+        Some(DiffKey {
+            target: self.diff_target()?,
+            settings: self.diff_settings,
+        })
+    }
+
+    fn diff_target(&self) -> Option<DiffTarget> {
+        if let Some((_, target)) = &self.pinned_diff {
+            return Some(target.clone());
+        }
+        match self.diff_source {
+            ActivePanel::Commits if let Some(files) = &self.commit_files => {
+                let commit = files.commit;
+                Some(match (files.view, files.selected_file()) {
+                    (FileView::Blame, Some(file)) => DiffTarget::Blame {
+                        commit,
+                        path: file.path.clone(),
+                    },
+                    (FileView::History, Some(file)) => DiffTarget::History {
+                        commit,
+                        path: file.path.clone(),
+                    },
+                    _ => DiffTarget::Commit(commit),
+                })
+            }
             ActivePanel::Commits => {
-                if let Some(selected) = self.commit_list_state.selected() {
-                    let commit_info = self.commits[selected].clone();
-                    git::get_commit_diff(&self.repo, &commit_info)
-                } else {
-                    Ok(Vec::new())
-                }
+                let commit = self.selected_commit_id()?;
+                Some(match self.marked_commit {
+                    Some(marked) if marked != commit => DiffTarget::Range {
+                        from: marked,
+                        to: commit,
+                    },
+                    _ if self.merge_view != MergeView::Parent(0) => DiffTarget::Merge {
+                        commit,
+                        view: self.merge_view,
+                    },
+                    _ => DiffTarget::Commit(commit),
+                })
             }
-            ActivePanel::Status => {
-                if let Some(selected) = self.status_list_state.selected() {
-                    if let Some(file_info) = self.status_files.get(selected) {
-                        git::get_file_diff(&self.repo, file_info)
-                    } else {
-                        Ok(Vec::new())
+            ActivePanel::Status => Some(match self.selected_status_row()? {
+                StatusRow::File { index, .. } => {
+                    let file = &self.status_files[*index];
+                    DiffTarget::File {
+                        path: file.path.clone(),
+                        side: file.side,
+                        index_id: git::index_entry_id(&self.repo, &file.path),
                     }
-                } else {
-                    Ok(Vec::new())
                 }
+                StatusRow::Dir { side, path, .. } => DiffTarget::File {
+                    path: path.clone(),
+                    side: *side,
+                    index_id: None,
+                },
+                StatusRow::Header { side, .. } => DiffTarget::File {
+                    path: String::new(),
+                    side: *side,
+                    index_id: None,
+                },
+            }),
+            ActivePanel::Diff => None,
+        }
+    }
+
+    /// Shows the diff for the current selection, from the cache when possible
+    /// and otherwise by asking the background worker for it.
+    pub fn update_diff(&mut self) {
+        let Some(key) = self.diff_key() else {
+            self.diff_worker.cancel();
+            self.diff_loading = false;
+            self.show_diff(None, SharedDiff::default());
+            return;
+        };
+        if let Some(diff) = self.diff_cache.get(&key) {
+            self.diff_worker.cancel();
+            self.diff_loading = false;
+            self.adopt_commit_files_diff(&key, &diff);
+            self.show_diff(Some(key.target), diff);
+            return;
+        }
+        let reload = self.diff_shown.as_ref() == Some(&key.target);
+        self.diff_worker.request(key);
+        if !reload {
+            self.diff_loading = true;
+            self.show_diff(None, SharedDiff::default());
+        }
+    }
+
+    pub fn handle_diff_response(&mut self, response: DiffResponse) {
+        let is_current = self.diff_worker.is_current(&response);
+        let target = response.key.target.clone();
+        let diff = match response.result {
+            Ok(text) => {
+                let diff = SharedDiff::new(text);
+                self.adopt_commit_files_diff(&response.key, &diff);
+                // A superseded response may predate `invalidate_files`, so
+                // caching it could bring back a diff of edited files.
+                if is_current {
+                    self.diff_cache
+                        .insert(response.key, SharedDiff::clone(&diff));
+                }
+                diff
             }
+            Err(e) => SharedDiff::new(DiffInfo::message(format!("Could not load diff: {e}"))),
         };
+        if is_current {
+            self.diff_loading = false;
+            self.show_diff(Some(target), diff);
+        }
+    }
 
-        self.diff_text = match diff_result {
-            Ok(spans) => spans,
-            // CORRECTED: Use modern f-string style formatting
-            Err(e) => vec![Spans::from(format!("Could not load diff: {e}"))],
+    /// Fills the commit file list once the diff of its commit arrives, and
+    /// refreshes it when diff settings change the set of files.
+    fn adopt_commit_files_diff(&mut self, key: &DiffKey, diff: &SharedDiff) {
+        let Some(files) = &mut self.commit_files else {
+            return;
         };
+        if key.target != DiffTarget::Commit(files.commit) {
+            return;
+        }
+        files.diff = Some(SharedDiff::clone(diff));
+        let last = diff.files.len().checked_sub(1);
+        let selected = files.list_state.selected().unwrap_or(0);
+        files.list_state.select(last.map(|last| selected.min(last)));
     }
 
-    fn refresh_all(&mut self) {
-        self.commits = git::fetch_log(&self.repo).unwrap_or_default();
+    /// The one file of the diff to show, when a file of a commit is picked.
+    fn diff_file_filter(&self) -> Option<usize> {
+        let files = self.commit_files.as_ref()?;
+        if self.diff_source != ActivePanel::Commits || files.view != FileView::Patch {
+            return None;
+        }
+        files.list_state.selected()
+    }
+
+    /// Displays `diff`, the diff of `target`. A new diff of the target already
+    /// shown, say after a file was saved, keeps the scroll position.
+    fn show_diff(&mut self, target: Option<DiffTarget>, diff: SharedDiff) {
+        self.diff_rows = DiffRows::new(&diff, self.diff_layout, self.diff_file_filter());
+        self.diff_text = diff;
+        if target.is_some() && target == self.diff_shown {
+            self.scroll_diff_to(self.diff_scroll);
+        } else {
+            self.diff_scroll = 0;
+            self.diff_hscroll = 0;
+        }
+        self.diff_shown = target;
+    }
+
+    pub fn handle_repo_change(&mut self, change: RepoChange) {
+        if let Some(watcher) = &mut self.watcher
+            && let Err(e) = watcher.watch_new_dirs(&change)
+        {
+            self.notify(
+                Severity::Warning,
+                format!("Not watching some new directories: {e}"),
+            );
+        }
+        if change.refs_changed {
+            self.refresh_all();
+            return;
+        }
+        let worktree_changed = change
+            .worktree_paths
+            .iter()
+            .any(|path| !self.repo.status_should_ignore(path).unwrap_or(false));
+        if change.index_changed || worktree_changed {
+            self.refresh_status();
+            let shows_workspace =
+                matches!(self.pinned_diff, Some((_, DiffTarget::Workspace { .. })));
+            if self.diff_source == ActivePanel::Status || shows_workspace {
+                self.update_diff();
+            }
+        }
+    }
+
+    pub fn handle_log_page(&mut self, page: Result<LogPage, git2::Error>) {
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                self.log_loader.page_received(true);
+                // Keep the log shown before the failed reload.
+                self.log_restart = None;
+                self.notify(Severity::Error, format!("Could not load commit log: {e}"));
+                return;
+            }
+        };
+        self.log_loader.page_received(page.done);
+        let Some(mut restart) = self.log_restart.take() else {
+            let was_empty = self.commits.is_empty();
+            self.commits.extend(page.commits);
+            if was_empty && !self.commits.is_empty() {
+                self.commit_list_state.select(Some(0));
+                if let ActivePanel::Commits = self.diff_source {
+                    self.update_diff();
+                }
+            }
+            return;
+        };
+
+        // Give up looking for the old selection once it has clearly moved or vanished.
+        const SEARCH_MARGIN: usize = 1000;
+        restart.commits.extend(page.commits);
+        let position = restart
+            .selected_id
+            .as_ref()
+            .and_then(|id| restart.commits.iter().position(|c| &c.id == id));
+        let keep_searching = position.is_none()
+            && restart.selected_id.is_some()
+            && restart.commits.len() < restart.selected_index + SEARCH_MARGIN
+            && !self.log_loader.done;
+        if keep_searching {
+            self.log_restart = Some(restart);
+            self.log_loader.request_more();
+            return;
+        }
+
+        self.commits = restart.commits;
         if self.commits.is_empty() {
             self.commit_list_state.select(None);
-        } else if self.commit_list_state.selected().is_none() {
-            self.commit_list_state.select(Some(0));
+        } else {
+            let index = position.unwrap_or(restart.selected_index);
+            self.commit_list_state
+                .select(Some(index.min(self.commits.len() - 1)));
+        }
+        if let ActivePanel::Commits = self.diff_source {
+            self.update_diff();
+        }
+    }
+
+    fn load_more_commits_if_needed(&mut self) {
+        const PREFETCH_MARGIN: usize = 50;
+        let selected = self.commit_list_state.selected().unwrap_or(0);
+        if selected + PREFETCH_MARGIN >= self.commits.len() {
+            self.log_loader.request_more();
         }
+    }
+
+    fn refresh_all(&mut self) {
+        let selected_index = self.commit_list_state.selected().unwrap_or(0);
+        let selected_id = self.commits.get(selected_index).map(|c| c.id.clone());
+        self.log_restart = Some(LogRestart {
+            selected_id,
+            selected_index,
+            commits: Vec::new(),
+        });
+        self.log_loader = LogLoader::start(self.repo.path().to_path_buf());
+        self.diff_cache.invalidate_refs();
+        self.refresh_head();
         self.refresh_status();
         self.update_diff();
     }
 
+    fn refresh_head(&mut self) {
+        self.branch_status = git::branch_status(&self.repo);
+        self.repo_state = self.repo.state();
+    }
+
     fn refresh_status(&mut self) {
-        self.status_files = git::fetch_status(&self.repo).unwrap_or_default();
-        if self.status_files.is_empty() {
-            self.status_list_state.select(None);
-        } else {
-            let selected_index = self.status_list_state.selected().unwrap_or(0);
-            if selected_index >= self.status_files.len() {
-                self.status_list_state
-                    .select(Some(self.status_files.len() - 1));
+        // Taken before the files are replaced, as rows index into them.
+        let selected = self.selected_status_key();
+        match git::fetch_status(&self.repo) {
+            Ok(files) => self.status_files = files,
+            Err(e) => {
+                self.notify(Severity::Error, format!("Could not read the status: {e}"));
+                return;
             }
         }
+        self.diff_cache.invalidate_files();
+        let present: HashSet<(&str, StatusSide)> = self
+            .status_files
+            .iter()
+            .map(|s| (s.path.as_str(), s.side))
+            .collect();
+        self.status_marks
+            .retain(|(path, side)| present.contains(&(path.as_str(), *side)));
+        // Forget folded directories once nothing below them is listed.
+        self.collapsed_dirs.retain(|(side, dir)| {
+            present.iter().any(|(path, s)| {
+                s == side
+                    && path
+                        .strip_prefix(dir.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+        });
+        self.rebuild_status_rows(selected);
+    }
+
+    fn selected_status_key(&self) -> Option<StatusRowKey> {
+        self.selected_status_row()
+            .map(|row| status_row_key(&self.status_files, row))
+    }
+
+    /// Lays out `status_rows` afresh, keeping the selection on the row
+    /// `selected` identifies if it is still shown.
+    fn rebuild_status_rows(&mut self, selected: Option<StatusRowKey>) {
+        self.status_rows =
+            status_tree::build_rows(&self.status_files, self.status_tree, &self.collapsed_dirs);
+        let selected_index = self.status_list_state.selected().unwrap_or(0);
+        let index = selected
+            .and_then(|key| {
+                self.status_rows
+                    .iter()
+                    .position(|row| status_row_key(&self.status_files, row) == key)
+            })
+            .unwrap_or_else(|| selected_index.min(self.status_rows.len() - 1));
+        self.status_list_state.select(Some(index));
+    }
+
+    /// Whether moving past either end of the focused list wraps around.
+    /// The commit log only does once it is fully loaded; until then its last
+    /// row is not the real end.
+    fn can_wrap(&self) -> bool {
+        !matches!(self.active_panel, ActivePanel::Commits)
+            || self.commit_files.is_some()
+            || self.log_loader.done
     }
 
     fn select_next(&mut self) {
+        let can_wrap = self.can_wrap();
         let (list_len, state) = match self.active_panel {
+            ActivePanel::Commits if let Some(files) = &mut self.commit_files => {
+                (files.files().len(), &mut files.list_state)
+            }
             ActivePanel::Commits => (self.commits.len(), &mut self.commit_list_state),
-            ActivePanel::Status => (self.status_files.len(), &mut self.status_list_state),
+            ActivePanel::Status => (self.status_rows.len(), &mut self.status_list_state),
+            ActivePanel::Diff => return,
         };
         if list_len == 0 {
             return;
         }
-        let i = state
-            .selected()
-            .map_or(0, |i| if i >= list_len - 1 { 0 } else { i + 1 });
+        let i = state.selected().map_or(0, |i| {
+            if i < list_len - 1 {
+                i + 1
+            } else if can_wrap {
+                0
+            } else {
+                i
+            }
+        });
         state.select(Some(i));
-        self.update_diff();
+        self.selection_moved();
     }
 
     fn select_previous(&mut self) {
+        let can_wrap = self.can_wrap();
         let (list_len, state) = match self.active_panel {
+            ActivePanel::Commits if let Some(files) = &mut self.commit_files => {
+                (files.files().len(), &mut files.list_state)
+            }
             ActivePanel::Commits => (self.commits.len(), &mut self.commit_list_state),
-            ActivePanel::Status => (self.status_files.len(), &mut self.status_list_state),
+            ActivePanel::Status => (self.status_rows.len(), &mut self.status_list_state),
+            ActivePanel::Diff => return,
         };
         if list_len == 0 {
             return;
         }
-        let i = state
-            .selected()
-            .map_or(0, |i| if i == 0 { list_len - 1 } else { i - 1 });
+        let i = state.selected().map_or(0, |i| {
+            if i > 0 {
+                i - 1
+            } else if can_wrap {
+                list_len - 1
+            } else {
+                0
+            }
+        });
         state.select(Some(i));
+        self.selection_moved();
+    }
+
+    /// Selects row `index` of the focused list, or its last row.
+    fn select_index(&mut self, index: usize) {
+        let (list_len, state) = match self.active_panel {
+            ActivePanel::Commits if let Some(files) = &mut self.commit_files => {
+                (files.files().len(), &mut files.list_state)
+            }
+            ActivePanel::Commits => (self.commits.len(), &mut self.commit_list_state),
+            ActivePanel::Status => (self.status_rows.len(), &mut self.status_list_state),
+            ActivePanel::Diff => return,
+        };
+        if list_len == 0 {
+            return;
+        }
+        state.select(Some(index.min(list_len - 1)));
+        self.selection_moved();
+    }
+
+    fn selection_moved(&mut self) {
+        if self.active_panel == ActivePanel::Commits && self.commit_files.is_none() {
+            self.load_more_commits_if_needed();
+        }
+        self.pinned_diff = None;
+        self.merge_view = MergeView::Parent(0);
         self.update_diff();
     }
+
+    pub fn handle_mouse_event(&mut self, mouse: MouseEvent) {
+        if !matches!(self.mode, AppMode::Normal) {
+            return;
+        }
+        let (column, row) = (mouse.column, mouse.row);
+        let Some(panel) = [ActivePanel::Commits, ActivePanel::Status, ActivePanel::Diff]
+            .into_iter()
+            .find(|&panel| contains(self.panel_area(panel), column, row))
+        else {
+            return;
+        };
+        match mouse.kind {
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                let down = matches!(mouse.kind, MouseEventKind::ScrollDown);
+                if panel == ActivePanel::Diff {
+                    let line = if down {
+                        self.diff_scroll + WHEEL_STEP
+                    } else {
+                        self.diff_scroll.saturating_sub(WHEEL_STEP)
+                    };
+                    self.scroll_diff_to(line);
+                    return;
+                }
+                self.focus_panel(panel);
+                let selected = self.list_selection().unwrap_or(0);
+                self.select_index(if down {
+                    selected + WHEEL_STEP
+                } else {
+                    selected.saturating_sub(WHEEL_STEP)
+                });
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                let now = Instant::now();
+                let double_click = self.last_click.is_some_and(|(at, c, r)| {
+                    (c, r) == (column, row) && now.duration_since(at) < DOUBLE_CLICK
+                });
+                self.last_click = Some((now, column, row));
+                self.focus_panel(panel);
+                if panel == ActivePanel::Diff {
+                    return;
+                }
+                // The first row inside the border is the first visible item.
+                let area = self.panel_area(panel);
+                if row == area.y || row + 1 >= area.y + area.height {
+                    return;
+                }
+                let index = self.list_offset() + usize::from(row - area.y - 1);
+                if index >= self.list_len() {
+                    return;
+                }
+                if self.list_selection() != Some(index) {
+                    self.select_index(index);
+                }
+                if double_click && panel == ActivePanel::Status {
+                    self.toggle_stage_selection();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn panel_area(&self, panel: ActivePanel) -> Rect {
+        match panel {
+            ActivePanel::Commits => self.commits_area,
+            ActivePanel::Status => self.status_area,
+            ActivePanel::Diff => self.diff_area,
+        }
+    }
+
+    fn list_len(&self) -> usize {
+        match self.active_panel {
+            ActivePanel::Commits => match &self.commit_files {
+                Some(files) => files.files().len(),
+                None => self.commits.len(),
+            },
+            ActivePanel::Status => self.status_rows.len(),
+            ActivePanel::Diff => 0,
+        }
+    }
+
+    fn list_offset(&self) -> usize {
+        match self.active_panel {
+            ActivePanel::Commits => match &self.commit_files {
+                Some(files) => files.list_offset,
+                None => self.commit_list_offset,
+            },
+            ActivePanel::Status => self.status_list_offset,
+            ActivePanel::Diff => 0,
+        }
+    }
+
+    fn list_selection(&self) -> Option<usize> {
+        match self.active_panel {
+            ActivePanel::Commits => match &self.commit_files {
+                Some(files) => files.list_state.selected(),
+                None => self.commit_list_state.selected(),
+            },
+            ActivePanel::Status => self.status_list_state.selected(),
+            ActivePanel::Diff => None,
+        }
+    }
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.x + area.width && row >= area.y && row < area.y + area.height
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Identifies a status row across rebuilds: its side, its path (empty for a
+/// heading) and whether it is a file.
+type StatusRowKey = (StatusSide, String, bool);
+
+/// The key of `row`, which must index into `files`.
+fn status_row_key(files: &[StatusInfo], row: &StatusRow) -> StatusRowKey {
+    match row {
+        StatusRow::Header { side, .. } => (*side, String::new(), false),
+        StatusRow::Dir { side, path, .. } => (*side, path.clone(), false),
+        StatusRow::File { index, .. } => {
+            let file = &files[*index];
+            (file.side, file.path.clone(), true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn shrinking_status_keeps_a_valid_selection() {
+        let dir = std::env::temp_dir().join(format!("termigit-test-app-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+        let mut app = App::new(repo).unwrap();
+        let last = app.status_rows.len() - 1;
+        app.status_list_state.select(Some(last));
+        assert!(matches!(app.status_rows[last], StatusRow::File { .. }));
+
+        std::fs::remove_file(dir.join("b")).unwrap();
+        std::fs::remove_file(dir.join("c")).unwrap();
+        app.refresh_status();
+        let selected = app.status_list_state.selected().unwrap();
+        assert!(selected < app.status_rows.len());
+        assert_eq!(app.status_files.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,294 @@
+// src/combined_diff.rs
+
+use crate::types::diff_info::{DiffHunk, DiffLine, DiffLineKind, FileDiff};
+use crate::types::diff_settings::Whitespace;
+use similar::{Algorithm, DiffOp, capture_diff_slices};
+use std::ops::Range;
+
+/// One line of a combined diff before it is cut into hunks.
+struct Row {
+    /// One column per parent: '+' if the result line is missing from that
+    /// parent, '-' if the parent line is missing from the result.
+    markers: Vec<char>,
+    old_lineno: Option<u32>,
+    new_lineno: Option<u32>,
+    content: String,
+}
+
+impl Row {
+    /// Differs from every parent, i.e. no parent can explain this line.
+    fn is_interesting(&self) -> bool {
+        self.markers.iter().all(|&m| m != ' ')
+    }
+}
+
+/// Builds a condensed combined diff of one file, like `git diff --cc`: the
+/// merge result against each of its parents at once, keeping only the hunks
+/// around lines that match none of the parents. Those lines are emphasized.
+/// Lines are compared with `whitespace` applied, as in the other diffs.
+pub fn combine_file(
+    path: &str,
+    parents: &[String],
+    result: &str,
+    algorithm: Algorithm,
+    context: usize,
+    whitespace: Whitespace,
+) -> FileDiff {
+    let new_lines: Vec<&str> = result.lines().collect();
+    let new_keys: Vec<String> = new_lines.iter().map(|l| normalize(l, whitespace)).collect();
+    let count = parents.len();
+    // For each result line, which parents lack it; and for each position in
+    // the result, the parent lines removed just before it.
+    let mut added = vec![vec![' '; count]; new_lines.len()];
+    let mut removed: Vec<Vec<Row>> = (0..=new_lines.len()).map(|_| Vec::new()).collect();
+    for (parent_index, parent) in parents.iter().enumerate() {
+        let old_lines: Vec<&str> = parent.lines().collect();
+        let old_keys: Vec<String> = old_lines.iter().map(|l| normalize(l, whitespace)).collect();
+        for op in capture_diff_slices(algorithm, &old_keys, &new_keys) {
+            let (old_range, new_range) = match op {
+                DiffOp::Equal { .. } => continue,
+                _ => (op.old_range(), op.new_range()),
+            };
+            for markers in &mut added[new_range.clone()] {
+                markers[parent_index] = '+';
+            }
+            let anchor = &mut removed[new_range.start];
+            for old in old_range {
+                // The same line dropped from several parents is shown once.
+                let existing = anchor.iter_mut().find(|row| {
+                    normalize(&row.content, whitespace) == old_keys[old]
+                        && row.markers[parent_index] == ' '
+                });
+                match existing {
+                    Some(row) => row.markers[parent_index] = '-',
+                    None => {
+                        let mut markers = vec![' '; count];
+                        markers[parent_index] = '-';
+                        anchor.push(Row {
+                            markers,
+                            old_lineno: Some(old as u32 + 1),
+                            new_lineno: None,
+                            content: old_lines[old].to_string(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    for (index, markers) in added.into_iter().enumerate() {
+        rows.append(&mut removed[index]);
+        rows.push(Row {
+            markers,
+            old_lineno: None,
+            new_lineno: Some(index as u32 + 1),
+            content: new_lines[index].to_string(),
+        });
+    }
+    rows.append(&mut removed[new_lines.len()]);
+
+    let at_signs = "@".repeat(count + 1);
+    let mut file = FileDiff {
+        path: path.to_string(),
+        status: 'M',
+        header: vec![format!("diff --cc {path}")],
+        hunks: Vec::new(),
+    };
+    for range in hunk_ranges(&rows, context) {
+        let first_new = rows[range.clone()].iter().find_map(|row| row.new_lineno);
+        let new_count = rows[range.clone()]
+            .iter()
+            .filter(|row| row.new_lineno.is_some())
+            .count();
+        let lines = rows[range]
+            .iter()
+            .map(|row| {
+                let kind = if row.markers.contains(&'-') {
+                    DiffLineKind::Removed
+                } else if row.markers.contains(&'+') {
+                    DiffLineKind::Added
+                } else {
+                    DiffLineKind::Context
+                };
+                let content = row.content.replace('\t', "    ");
+                let whole_line = 0..content.len();
+                DiffLine {
+                    kind,
+                    old_lineno: row.old_lineno,
+                    new_lineno: row.new_lineno,
+                    emphasis: if row.is_interesting() {
+                        vec![whole_line]
+                    } else {
+                        Vec::new()
+                    },
+                    content,
+                    syntax: Vec::new(),
+                    markers: Some(row.markers.iter().collect()),
+                }
+            })
+            .collect();
+        file.hunks.push(DiffHunk {
+            header: format!(
+                "{at_signs} +{},{new_count} {at_signs}",
+                first_new.unwrap_or(0)
+            ),
+            lines,
+        });
+    }
+    file
+}
+
+/// What a line is compared by: itself, or with runs of whitespace made one
+/// space and trailing whitespace dropped, or with all whitespace removed.
+fn normalize(line: &str, whitespace: Whitespace) -> String {
+    match whitespace {
+        Whitespace::Show => line.to_string(),
+        Whitespace::IgnoreChange => {
+            let mut key = String::with_capacity(line.len());
+            let mut in_space = false;
+            for c in line.trim_end().chars() {
+                if c.is_whitespace() {
+                    in_space = true;
+                    continue;
+                }
+                if in_space {
+                    key.push(' ');
+                    in_space = false;
+                }
+                key.push(c);
+            }
+            key
+        }
+        Whitespace::IgnoreAll => line.chars().filter(|c| !c.is_whitespace()).collect(),
+    }
+}
+
+/// The row ranges within `context` rows of an interesting row, merged where
+/// they touch.
+fn hunk_ranges(rows: &[Row], context: usize) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (index, _) in rows
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| row.is_interesting())
+    {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(rows.len());
+        match ranges.last_mut() {
+            Some(last) if last.end >= start => last.end = end,
+            _ => ranges.push(start..end),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combine(parents: &[&str], result: &str, context: usize) -> FileDiff {
+        let parents: Vec<String> = parents.iter().map(|p| p.to_string()).collect();
+        combine_file(
+            "f",
+            &parents,
+            result,
+            Algorithm::Myers,
+            context,
+            Whitespace::Show,
+        )
+    }
+
+    /// Each line of each hunk as its markers and content, e.g. `"+ x"`.
+    fn lines(file: &FileDiff) -> Vec<Vec<String>> {
+        file.hunks
+            .iter()
+            .map(|hunk| {
+                hunk.lines
+                    .iter()
+                    .map(|line| format!("{}{}", line.markers.as_deref().unwrap(), line.content))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn rows(markers: &[&str]) -> Vec<Row> {
+        markers
+            .iter()
+            .map(|m| Row {
+                markers: m.chars().collect(),
+                old_lineno: None,
+                new_lineno: None,
+                content: String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clean_merge_has_no_hunks() {
+        let file = combine(
+            &["a\nB\nc\nd\ne\n", "a\nb\nc\nD\ne\n"],
+            "a\nB\nc\nD\ne\n",
+            3,
+        );
+        assert!(file.hunks.is_empty());
+    }
+
+    #[test]
+    fn line_matching_no_parent_is_shown_with_context() {
+        let file = combine(&["x\n1\ny\n", "x\n2\ny\n"], "x\n3\ny\n", 3);
+        assert_eq!(file.header, ["diff --cc f"]);
+        assert_eq!(file.hunks.len(), 1);
+        assert_eq!(file.hunks[0].header, "@@@ +1,3 @@@");
+        assert_eq!(lines(&file), [["  x", "- 1", " -2", "++3", "  y"]]);
+        let emphasized: Vec<&str> = file.hunks[0]
+            .lines
+            .iter()
+            .filter(|line| !line.emphasis.is_empty())
+            .map(|line| line.content.as_str())
+            .collect();
+        assert_eq!(emphasized, ["3"]);
+    }
+
+    #[test]
+    fn line_dropped_from_every_parent_is_shown_once() {
+        let file = combine(&["a\nold\nb\n", "a\nold\nb\n"], "a\nb\n", 1);
+        assert_eq!(lines(&file), [["  a", "--old", "  b"]]);
+        let old = &file.hunks[0].lines[1];
+        assert!(old.kind == DiffLineKind::Removed);
+        assert_eq!((old.old_lineno, old.new_lineno), (Some(2), None));
+    }
+
+    #[test]
+    fn ignored_whitespace_counts_as_matching_a_parent() {
+        let parents = ["x\n1\ny\n".to_string(), "x\n 1\ny\n".to_string()];
+        let combine =
+            |whitespace| combine_file("f", &parents, "x\n1 \ny\n", Algorithm::Myers, 3, whitespace);
+        assert_eq!(combine(Whitespace::Show).hunks.len(), 1);
+        assert!(combine(Whitespace::IgnoreChange).hunks.is_empty());
+        assert!(combine(Whitespace::IgnoreAll).hunks.is_empty());
+    }
+
+    #[test]
+    fn hunk_ranges_merge_touching_context_and_stop_at_the_ends() {
+        let mut markers = vec!["  "; 12];
+        for index in [0, 4, 10] {
+            markers[index] = "++";
+        }
+        assert_eq!(hunk_ranges(&rows(&markers), 2), [0..7, 8..12]);
+
+        let mut markers = vec!["  "; 9];
+        markers[0] = "+-";
+        markers[5] = "-+";
+        assert_eq!(
+            hunk_ranges(&rows(&markers), 2),
+            vec![Range { start: 0, end: 8 }]
+        );
+    }
+
+    #[test]
+    fn hunk_ranges_skip_lines_some_parent_explains() {
+        let markers = ["  ", "+ ", " -", "  "];
+        assert!(hunk_ranges(&rows(&markers), 3).is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+// src/confirm.rs
+
+use git2::Repository;
+
+/// How many affected files a confirmation lists by name.
+const LISTED: usize = 8;
+
+/// Destructive actions that ask before they run.
+#[derive(Clone)]
+pub enum ConfirmAction {
+    /// Throw away the changes to these files, staged ones too with
+    /// `from_head`.
+    Discard { paths: Vec<String>, from_head: bool },
+}
+
+impl ConfirmAction {
+    /// The key under `termigit.confirm`; setting it to false skips the prompt.
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            ConfirmAction::Discard { .. } => "discard",
+        }
+    }
+
+    /// Whether the user wants to be asked; unreadable config asks.
+    pub fn needs_confirmation(&self, repo: &Repository) -> bool {
+        let key = format!("termigit.confirm.{}", self.config_name());
+        repo.config()
+            .and_then(|config| config.get_bool(&key))
+            .unwrap_or(true)
+    }
+}
+
+/// A destructive action waiting for an explicit yes, with what it will lose.
+pub struct Confirmation {
+    pub action: ConfirmAction,
+    pub title: String,
+    pub details: Vec<String>,
+}
+
+impl Confirmation {
+    pub fn new(action: ConfirmAction, title: String) -> Self {
+        Self {
+            action,
+            title,
+            details: Vec::new(),
+        }
+    }
+
+    /// Adds a heading followed by the first few `items`, indented.
+    pub fn list(&mut self, heading: String, items: impl ExactSizeIterator<Item = String>) {
+        let count = items.len();
+        self.details.push(heading);
+        self.details
+            .extend(items.take(LISTED).map(|item| format!("  {item}")));
+        if count > LISTED {
+            self.details
+                .push(format!("  … and {} more", count - LISTED));
+        }
+    }
+}
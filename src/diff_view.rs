@@ -0,0 +1,499 @@
+// src/diff_view.rs
+
+use crate::types::blame_info::BlameLine;
+use crate::types::commit_info::{CommitDetail, Identity};
+use crate::types::diff_info::{DiffInfo, DiffLine, DiffLineKind};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DiffLayout {
+    Unified,
+    SideBySide,
+}
+
+/// One screen row of a laid out diff, pointing back into the `DiffInfo`.
+enum DiffRow {
+    Preamble(usize),
+    FileHeader {
+        file: usize,
+        line: usize,
+    },
+    HunkHeader {
+        file: usize,
+        hunk: usize,
+    },
+    Line {
+        file: usize,
+        hunk: usize,
+        line: usize,
+    },
+    /// Old and new halves of a side-by-side row; either may be empty.
+    Pair {
+        file: usize,
+        hunk: usize,
+        old: Option<usize>,
+        new: Option<usize>,
+    },
+}
+
+/// The rows of a diff in a given layout, plus where each file and hunk starts.
+/// Only indices are stored; text is styled per visible row in `render_row`.
+#[derive(Default)]
+pub struct DiffRows {
+    rows: Vec<DiffRow>,
+    pub file_starts: Vec<usize>,
+    pub hunk_starts: Vec<usize>,
+}
+
+impl DiffRows {
+    /// Lays out the whole diff, or with `only_file` just that file's patch.
+    pub fn new(diff: &DiffInfo, layout: DiffLayout, only_file: Option<usize>) -> Self {
+        let mut rows = Self::default();
+        if only_file.is_none() {
+            rows.rows
+                .extend((0..diff.preamble.len()).map(DiffRow::Preamble));
+        }
+        for (file_index, file) in diff.files.iter().enumerate() {
+            if only_file.is_some_and(|only| only != file_index) {
+                continue;
+            }
+            rows.file_starts.push(rows.rows.len());
+            rows.rows
+                .extend((0..file.header.len()).map(|line| DiffRow::FileHeader {
+                    file: file_index,
+                    line,
+                }));
+            for (hunk_index, hunk) in file.hunks.iter().enumerate() {
+                rows.hunk_starts.push(rows.rows.len());
+                rows.rows.push(DiffRow::HunkHeader {
+                    file: file_index,
+                    hunk: hunk_index,
+                });
+                match layout {
+                    DiffLayout::Unified => {
+                        rows.rows
+                            .extend((0..hunk.lines.len()).map(|line| DiffRow::Line {
+                                file: file_index,
+                                hunk: hunk_index,
+                                line,
+                            }))
+                    }
+                    DiffLayout::SideBySide => {
+                        rows.rows
+                            .extend(hunk.paired_lines().into_iter().map(|(old, new)| {
+                                DiffRow::Pair {
+                                    file: file_index,
+                                    hunk: hunk_index,
+                                    old,
+                                    new,
+                                }
+                            }))
+                    }
+                }
+            }
+        }
+        rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Styles row `index` for a panel `width` columns wide. Side-by-side rows
+    /// are clipped here, skipping `hscroll` columns of each half; unified rows
+    /// are left whole for the paragraph to scroll or wrap.
+    pub fn render_row<'a>(
+        &self,
+        diff: &'a DiffInfo,
+        index: usize,
+        width: usize,
+        hscroll: usize,
+    ) -> Spans<'a> {
+        let header_style = Style::default().fg(Color::Cyan);
+        match self.rows[index] {
+            DiffRow::Preamble(line) => borrow_spans(&diff.preamble[line]),
+            DiffRow::FileHeader { file, line } => {
+                Spans::from(Span::styled(&diff.files[file].header[line], header_style))
+            }
+            DiffRow::HunkHeader { file, hunk } => Spans::from(Span::styled(
+                &diff.files[file].hunks[hunk].header,
+                header_style,
+            )),
+            DiffRow::Line { file, hunk, line } => {
+                let line = &diff.files[file].hunks[hunk].lines[line];
+                let (origin, style) = line_style(line.kind);
+                let origin = line.markers.as_deref().unwrap_or(origin);
+                let mut spans = vec![Span::styled(origin, style)];
+                spans.extend(content_spans(line, style));
+                Spans::from(spans)
+            }
+            DiffRow::Pair {
+                file,
+                hunk,
+                old,
+                new,
+            } => {
+                let lines = &diff.files[file].hunks[hunk].lines;
+                let half = width.saturating_sub(1) / 2;
+                let mut spans = half_row(old.map(|i| &lines[i]), true, half, hscroll);
+                spans.push(Span::styled("│", Style::default().fg(Color::DarkGray)));
+                spans.extend(half_row(
+                    new.map(|i| &lines[i]),
+                    false,
+                    width.saturating_sub(half + 1),
+                    hscroll,
+                ));
+                Spans::from(spans)
+            }
+        }
+    }
+}
+
+fn line_style(kind: DiffLineKind) -> (&'static str, Style) {
+    match kind {
+        DiffLineKind::Added => ("+", Style::default().fg(Color::Green)),
+        DiffLineKind::Removed => ("-", Style::default().fg(Color::Red)),
+        DiffLineKind::Context => (" ", Style::default()),
+        DiffLineKind::Meta => ("", Style::default().fg(Color::DarkGray)),
+    }
+}
+
+fn half_row<'a>(
+    line: Option<&'a DiffLine>,
+    old: bool,
+    width: usize,
+    hscroll: usize,
+) -> Vec<Span<'a>> {
+    const NUMBER_WIDTH: usize = 6;
+    let Some(line) = line else {
+        return vec![Span::raw(" ".repeat(width))];
+    };
+    let number = if old {
+        line.old_lineno
+    } else {
+        line.new_lineno
+    };
+    let number = number.map_or_else(String::new, |n| n.to_string());
+    let (_, style) = line_style(line.kind);
+    let number_width = NUMBER_WIDTH.min(width);
+    let mut spans = vec![Span::styled(
+        format!("{number:>w$} ", w = number_width.saturating_sub(1)),
+        Style::default().fg(Color::DarkGray),
+    )];
+    spans.extend(clip(
+        content_spans(line, style),
+        hscroll,
+        width - number_width,
+    ));
+    spans
+}
+
+/// Splits a line's content into spans, layering syntax colors (if any) under
+/// the add/remove coloring and emphasizing the changed words.
+fn content_spans(line: &DiffLine, fallback: Style) -> Vec<Span<'_>> {
+    if line.syntax.is_empty() && line.emphasis.is_empty() {
+        return vec![Span::styled(line.content.as_str(), fallback)];
+    }
+    let (base, emphasized) = if line.syntax.is_empty() {
+        (
+            fallback,
+            fallback.add_modifier(Modifier::REVERSED | Modifier::BOLD),
+        )
+    } else {
+        let (tint, strong) = match line.kind {
+            DiffLineKind::Added => (Color::Rgb(0x1f, 0x3d, 0x1f), Color::Rgb(0x2f, 0x6f, 0x2f)),
+            DiffLineKind::Removed => (Color::Rgb(0x4a, 0x1f, 0x1f), Color::Rgb(0x8a, 0x2f, 0x2f)),
+            _ => (Color::Reset, Color::Reset),
+        };
+        (
+            Style::default().bg(tint),
+            Style::default().bg(strong).add_modifier(Modifier::BOLD),
+        )
+    };
+
+    let mut breaks: Vec<usize> = line
+        .syntax
+        .iter()
+        .map(|&(start, _)| start)
+        .chain(line.emphasis.iter().flat_map(|r| [r.start, r.end]))
+        .chain([0, line.content.len()])
+        .collect();
+    breaks.sort_unstable();
+    breaks.dedup();
+
+    let mut spans = Vec::with_capacity(breaks.len());
+    let mut color = None;
+    let mut next_color = line.syntax.iter().peekable();
+    for window in breaks.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        while let Some(&&(offset, c)) = next_color.peek() {
+            if offset > start {
+                break;
+            }
+            color = Some(c);
+            next_color.next();
+        }
+        let is_emphasized = line.emphasis.iter().any(|r| r.contains(&start));
+        let mut style = if is_emphasized { emphasized } else { base };
+        if let Some(color) = color {
+            style = style.fg(color);
+        }
+        spans.push(Span::styled(&line.content[start..end], style));
+    }
+    if spans.is_empty() {
+        spans.push(Span::styled("", base));
+    }
+    spans
+}
+
+/// Drops the first `skip` columns of `spans` and pads or cuts them to `width`.
+fn clip<'a>(spans: Vec<Span<'a>>, skip: usize, width: usize) -> Vec<Span<'a>> {
+    let mut out = Vec::with_capacity(spans.len() + 1);
+    let mut column = 0;
+    let mut used = 0;
+    for span in spans {
+        let mut text = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or(0);
+            if column < skip {
+                column += w;
+                continue;
+            }
+            if used + w > width {
+                break;
+            }
+            text.push(c);
+            used += w;
+        }
+        if !text.is_empty() {
+            out.push(Span::styled(text, span.style));
+        }
+    }
+    out.push(Span::raw(" ".repeat(width - used)));
+    out
+}
+
+/// Builds the commit header shown above a commit's diff: ids, refs, people,
+/// the message, its trailers and a diffstat of `diff`.
+pub fn commit_preamble(detail: &CommitDetail, diff: &DiffInfo) -> Vec<Spans<'static>> {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut lines = vec![Spans::from(vec![
+        Span::styled("commit ", Style::default().fg(Color::Yellow)),
+        Span::styled(detail.id.clone(), Style::default().fg(Color::Yellow)),
+    ])];
+    if !detail.refs.is_empty() {
+        lines.push(Spans::from(vec![
+            Span::styled("Refs:      ", label),
+            Span::styled(
+                detail.refs.join(", "),
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]));
+    }
+    if !detail.parents.is_empty() {
+        lines.push(Spans::from(vec![
+            Span::styled("Parents:   ", label),
+            Span::styled(detail.parents.join(" "), Style::default().fg(Color::Yellow)),
+        ]));
+    }
+    lines.push(identity_line("Author:    ", &detail.author));
+    lines.push(identity_line("Committer: ", &detail.committer));
+    lines.push(Spans::default());
+    lines.extend(
+        detail
+            .message
+            .lines()
+            .map(|line| Spans::from(format!("    {line}"))),
+    );
+    if !detail.trailers.is_empty() {
+        lines.push(Spans::default());
+        lines.extend(detail.trailers.iter().map(|(key, value)| {
+            Spans::from(vec![
+                Span::raw("    "),
+                Span::styled(format!("{key}:"), Style::default().fg(Color::Cyan)),
+                Span::raw(format!(" {value}")),
+            ])
+        }));
+    }
+    lines.push(Spans::default());
+    lines.extend(diffstat(diff));
+    lines.push(Spans::default());
+    lines
+}
+
+/// Blame output as `commit date author lineno │ content` rows.
+pub fn blame_preamble(lines: &[BlameLine]) -> Vec<Spans<'static>> {
+    let author_width = lines
+        .iter()
+        .map(|line| line.author.width())
+        .max()
+        .unwrap_or(0)
+        .min(20);
+    let number_width = lines.len().to_string().len();
+    let mut previous = "";
+    lines
+        .iter()
+        .map(|line| {
+            // Only the first line of each run from the same commit is labelled.
+            let (commit, date, author) = if line.commit == previous {
+                (String::new(), String::new(), String::new())
+            } else {
+                let author = truncate_to_width(&line.author, author_width);
+                (line.commit.clone(), line.date.clone(), author)
+            };
+            previous = &line.commit;
+            Spans::from(vec![
+                Span::styled(format!("{commit:7} "), Style::default().fg(Color::Yellow)),
+                Span::styled(format!("{date:10} "), Style::default().fg(Color::Blue)),
+                Span::styled(
+                    format!(
+                        "{author}{} ",
+                        " ".repeat(author_width.saturating_sub(author.width()))
+                    ),
+                    Style::default().fg(Color::Green),
+                ),
+                Span::styled(
+                    format!("{:>number_width$} │ ", line.lineno),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(line.content.clone()),
+            ])
+        })
+        .collect()
+}
+
+/// The longest prefix of `text` that fits in `width` columns.
+fn truncate_to_width(text: &str, width: usize) -> String {
+    let mut used = 0;
+    text.chars()
+        .take_while(|c| {
+            used += c.width().unwrap_or(0);
+            used <= width
+        })
+        .collect()
+}
+
+fn identity_line(label: &'static str, identity: &Identity) -> Spans<'static> {
+    Spans::from(vec![
+        Span::styled(label, Style::default().fg(Color::DarkGray)),
+        Span::raw(format!("{} <{}>  ", identity.name, identity.email)),
+        Span::styled(identity.date.clone(), Style::default().fg(Color::Blue)),
+    ])
+}
+
+/// One `path | count +++--` line per file plus a summary, like `git show --stat`.
+fn diffstat(diff: &DiffInfo) -> Vec<Spans<'static>> {
+    const BAR_WIDTH: usize = 40;
+    let counts: Vec<(&str, usize, usize)> = diff
+        .files
+        .iter()
+        .map(|file| {
+            let (added, removed) = file.line_counts();
+            (file.path.as_str(), added, removed)
+        })
+        .collect();
+    let path_width = counts.iter().map(|(p, ..)| p.width()).max().unwrap_or(0);
+    let most = counts.iter().map(|(_, a, r)| a + r).max().unwrap_or(0);
+    let count_width = most.to_string().len();
+    let mut lines: Vec<Spans> = counts
+        .iter()
+        .map(|&(path, added, removed)| {
+            // Scale the bar down only when the largest change would overflow it.
+            let scale = |n: usize| {
+                if most <= BAR_WIDTH || n == 0 {
+                    n
+                } else {
+                    (n * BAR_WIDTH / most).max(1)
+                }
+            };
+            Spans::from(vec![
+                Span::raw(format!(
+                    " {path}{} | {:>count_width$} ",
+                    " ".repeat(path_width - path.width()),
+                    added + removed
+                )),
+                Span::styled("+".repeat(scale(added)), Style::default().fg(Color::Green)),
+                Span::styled("-".repeat(scale(removed)), Style::default().fg(Color::Red)),
+            ])
+        })
+        .collect();
+    let (added, removed) = counts.iter().fold((0, 0), |(a, r), &(_, added, removed)| {
+        (a + added, r + removed)
+    });
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    lines.push(Spans::from(format!(
+        " {} file{} changed, {added} insertion{}(+), {removed} deletion{}(-)",
+        counts.len(),
+        plural(counts.len()),
+        plural(added),
+        plural(removed)
+    )));
+    lines
+}
+
+/// Re-borrows cached, already styled lines so drawing a frame copies no text.
+pub fn borrow_spans<'a>(spans: &'a Spans<'static>) -> Spans<'a> {
+    Spans::from(
+        spans
+            .0
+            .iter()
+            .map(|span| Span::styled(span.content.as_ref(), span.style))
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blame_line(commit: &str, author: &str) -> BlameLine {
+        BlameLine {
+            lineno: 1,
+            commit: commit.to_string(),
+            author: author.to_string(),
+            date: "2024-01-01".to_string(),
+            content: "x".to_string(),
+        }
+    }
+
+    /// The author column of each row, padding included.
+    fn author_column(rows: &[Spans]) -> Vec<String> {
+        rows.iter()
+            .map(|row| row.0[2].content.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn blame_cuts_wide_authors_by_display_width() {
+        let rows = blame_preamble(&[
+            blame_line("1111111", "山田太郎山田太郎山田太郎山田太郎"),
+            blame_line("2222222", "Ann"),
+            blame_line("3333333", "🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀"),
+        ]);
+        let authors = author_column(&rows);
+        assert_eq!(
+            authors,
+            [
+                "山田太郎山田太郎山田 ",
+                "Ann                  ",
+                "🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀 "
+            ]
+        );
+        assert!(authors.iter().all(|author| author.width() == 21));
+    }
+
+    #[test]
+    fn truncate_to_width_never_splits_a_wide_character() {
+        assert_eq!(truncate_to_width("日本語", 5), "日本");
+        assert_eq!(truncate_to_width("日本語", 6), "日本語");
+        assert_eq!(truncate_to_width("abc", 0), "");
+    }
+}
@@ -0,0 +1,257 @@
+// src/diff_worker.rs
+
+use crate::diff_view;
+use crate::git;
+use crate::highlight;
+use crate::types::diff_info::DiffInfo;
+use crate::types::diff_settings::DiffSettings;
+use crate::types::status_info::StatusSide;
+use crate::word_diff;
+use git2::{Oid, Repository};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc as std_mpsc;
+use std::thread;
+use tokio::sync::mpsc;
+
+const CACHE_CAPACITY: usize = 64;
+
+pub type SharedDiff = Arc<DiffInfo>;
+
+/// What the diff panel should show.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DiffTarget {
+    /// A commit against its first parent.
+    Commit(Oid),
+    /// A merge commit against another parent, or all of them combined.
+    Merge { commit: Oid, view: MergeView },
+    /// From one commit's tree to another's.
+    Range { from: Oid, to: Oid },
+    /// One side of a changed file or directory (the whole side when `path`
+    /// is empty), keyed by the blob currently staged for a file so that
+    /// staging or unstaging never serves a stale diff.
+    File {
+        path: String,
+        side: StatusSide,
+        index_id: Option<Oid>,
+    },
+    /// From a commit's tree to the working tree, or to the index.
+    Workspace { commit: Oid, index_only: bool },
+    /// A file as of a commit, annotated line by line.
+    Blame { commit: Oid, path: String },
+    /// Every patch to a file in the history of a commit.
+    History { commit: Oid, path: String },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MergeView {
+    /// Against the parent with this index, counting from 0.
+    Parent(usize),
+    Combined,
+}
+
+/// A target together with the settings it is diffed with; the cache key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DiffKey {
+    pub target: DiffTarget,
+    pub settings: DiffSettings,
+}
+
+struct DiffRequest {
+    generation: u64,
+    key: DiffKey,
+}
+
+pub struct DiffResponse {
+    pub generation: u64,
+    pub key: DiffKey,
+    pub result: Result<DiffInfo, git2::Error>,
+}
+
+/// Computes diffs, including word emphasis and syntax colors, on a background
+/// thread with its own `Repository`. Only the most recent request is worked
+/// on: anything older is skipped, and a diff that is being formatted when a
+/// newer request arrives is abandoned.
+pub struct DiffWorker {
+    sender: std_mpsc::Sender<DiffRequest>,
+    latest: Arc<AtomicU64>,
+    pub receiver: mpsc::UnboundedReceiver<DiffResponse>,
+}
+
+impl DiffWorker {
+    pub fn start(repo_path: PathBuf) -> Self {
+        let (request_tx, request_rx) = std_mpsc::channel::<DiffRequest>();
+        let (response_tx, response_rx) = mpsc::unbounded_channel();
+        let latest = Arc::new(AtomicU64::new(0));
+        let worker_latest = Arc::clone(&latest);
+
+        thread::spawn(move || {
+            let repo = Repository::open(repo_path);
+            while let Ok(mut request) = request_rx.recv() {
+                while let Ok(newer) = request_rx.try_recv() {
+                    request = newer;
+                }
+                let cancelled = || worker_latest.load(Ordering::Relaxed) != request.generation;
+                if cancelled() {
+                    continue;
+                }
+                let result = match &repo {
+                    Ok(repo) => compute(repo, &request.key, &cancelled),
+                    Err(e) => Err(git2::Error::from_str(&format!("Failed to open repo: {e}"))),
+                };
+                if cancelled() {
+                    continue;
+                }
+                let response = DiffResponse {
+                    generation: request.generation,
+                    key: request.key,
+                    result,
+                };
+                if response_tx.send(response).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            sender: request_tx,
+            latest,
+            receiver: response_rx,
+        }
+    }
+
+    pub fn request(&mut self, key: DiffKey) {
+        let generation = self.latest.fetch_add(1, Ordering::Relaxed) + 1;
+        let _ = self.sender.send(DiffRequest { generation, key });
+    }
+
+    /// Abandons whatever the worker is doing, e.g. after a cache hit.
+    pub fn cancel(&mut self) {
+        self.latest.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn is_current(&self, response: &DiffResponse) -> bool {
+        self.latest.load(Ordering::Relaxed) == response.generation
+    }
+}
+
+fn compute(
+    repo: &Repository,
+    key: &DiffKey,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<DiffInfo, git2::Error> {
+    let settings = &key.settings;
+    let mut info = match &key.target {
+        DiffTarget::Commit(oid) => {
+            let info = git::get_commit_diff(repo, *oid, 0, settings, cancelled)?;
+            with_commit_header(repo, *oid, info)?
+        }
+        DiffTarget::Merge {
+            commit,
+            view: MergeView::Parent(parent),
+        } => {
+            let info = git::get_commit_diff(repo, *commit, *parent, settings, cancelled)?;
+            with_commit_header(repo, *commit, info)?
+        }
+        DiffTarget::Merge {
+            commit,
+            view: MergeView::Combined,
+        } => {
+            // Combined lines already carry their own emphasis.
+            let mut info = git::get_combined_diff(repo, *commit, settings, cancelled)?;
+            highlight::highlight(&mut info, cancelled);
+            return with_commit_header(repo, *commit, info);
+        }
+        DiffTarget::Range { from, to } => {
+            git::get_range_diff(repo, *from, *to, settings, cancelled)?
+        }
+        DiffTarget::File { path, side, .. } => {
+            git::get_file_diff(repo, path, *side, settings, cancelled)?
+        }
+        DiffTarget::Workspace { commit, index_only } => {
+            git::get_workspace_diff(repo, *commit, *index_only, settings, cancelled)?
+        }
+        DiffTarget::Blame { commit, path } => DiffInfo {
+            preamble: diff_view::blame_preamble(&git::blame_file(repo, *commit, path)?),
+            ..DiffInfo::default()
+        },
+        DiffTarget::History { commit, path } => {
+            git::file_history(repo, *commit, path, settings, cancelled)?
+        }
+    };
+    word_diff::mark_changes(&mut info);
+    highlight::highlight(&mut info, cancelled);
+    Ok(info)
+}
+
+fn with_commit_header(
+    repo: &Repository,
+    commit: Oid,
+    mut info: DiffInfo,
+) -> Result<DiffInfo, git2::Error> {
+    let detail = git::commit_detail(repo, commit)?;
+    info.preamble = diff_view::commit_preamble(&detail, &info);
+    Ok(info)
+}
+
+/// A small least-recently-used cache of formatted diffs.
+#[derive(Default)]
+pub struct DiffCache {
+    entries: HashMap<DiffKey, SharedDiff>,
+    order: VecDeque<DiffKey>,
+}
+
+impl DiffCache {
+    pub fn get(&mut self, key: &DiffKey) -> Option<SharedDiff> {
+        let diff = self.entries.get(key).cloned()?;
+        self.touch(key);
+        Some(diff)
+    }
+
+    pub fn insert(&mut self, key: DiffKey, diff: SharedDiff) {
+        if self.entries.insert(key.clone(), diff).is_some() {
+            self.touch(&key);
+            return;
+        }
+        self.order.push_back(key);
+        if self.order.len() > CACHE_CAPACITY
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.entries.remove(&oldest);
+        }
+    }
+
+    /// Drops working tree and index diffs, which go stale whenever files are
+    /// edited or staged.
+    pub fn invalidate_files(&mut self) {
+        self.remove_where(|target| {
+            matches!(
+                target,
+                DiffTarget::File { .. } | DiffTarget::Workspace { .. }
+            )
+        });
+    }
+
+    /// Drops commit diffs, whose header lists the branches and tags pointing
+    /// at the commit, after refs moved.
+    pub fn invalidate_refs(&mut self) {
+        self.remove_where(|target| {
+            matches!(target, DiffTarget::Commit(_) | DiffTarget::Merge { .. })
+        });
+    }
+
+    fn remove_where(&mut self, is_stale: impl Fn(&DiffTarget) -> bool) {
+        self.entries.retain(|key, _| !is_stale(&key.target));
+        self.order.retain(|key| !is_stale(&key.target));
+    }
+
+    fn touch(&mut self, key: &DiffKey) {
+        if let Some(position) = self.order.iter().position(|k| k == key)
+            && let Some(key) = self.order.remove(position)
+        {
+            self.order.push_back(key);
+        }
+    }
+}
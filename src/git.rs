@@ -1,128 +1,995 @@
 // src/git.rs
 
-use crate::types::{commit_info::CommitInfo, status_info::StatusInfo};
-use git2::{self, Commit, Repository, Sort};
+use crate::combined_diff;
+use crate::signing::Signer;
+use crate::types::blame_info::BlameLine;
+use crate::types::commit_info::{CommitDetail, CommitInfo, Identity};
+use crate::types::diff_info::{DiffHunk, DiffInfo, DiffLine, DiffLineKind, FileDiff};
+use crate::types::diff_settings::{DiffAlgorithm, DiffSettings, Whitespace};
+use crate::types::repo_summary::{BranchStatus, RepoSummary};
+use crate::types::status_info::{StatusInfo, StatusSide};
+use git2::{self, Commit, Repository, Sort, Status};
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::path::Path;
-use tui::style::{Color, Style};
-use tui::text::{Span, Spans};
 
-pub fn fetch_log(repo: &Repository) -> Result<Vec<CommitInfo>, git2::Error> {
+/// Returns a time-sorted revwalk from HEAD, or `None` on an unborn branch.
+pub fn log_walk(repo: &Repository) -> Result<Option<git2::Revwalk<'_>>, git2::Error> {
+    // Pushing an unborn HEAD fails with a generic error, so check it first.
+    match repo.head() {
+        Ok(_) => {}
+        Err(e)
+            if matches!(
+                e.code(),
+                git2::ErrorCode::UnbornBranch | git2::ErrorCode::NotFound
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    }
     let mut revwalk = repo.revwalk()?;
     revwalk.push_head()?;
     revwalk.set_sorting(Sort::TIME)?;
-    let mut commits = Vec::new();
-    for oid in revwalk {
-        let oid = oid?;
-        let commit: Commit = repo.find_commit(oid)?;
-        let author = commit.author();
-        commits.push(CommitInfo {
-            id: oid.to_string(),
-            message: commit.summary().unwrap_or("No commit message").to_string(),
-            author: author.name().unwrap_or("Unknown").to_string(),
+    Ok(Some(revwalk))
+}
+pub fn commit_info(repo: &Repository, oid: git2::Oid) -> Result<CommitInfo, git2::Error> {
+    let commit: Commit = repo.find_commit(oid)?;
+    let author = commit.author();
+    Ok(CommitInfo {
+        id: oid.to_string(),
+        message: commit.summary().unwrap_or("No commit message").to_string(),
+        author: author.name().unwrap_or("Unknown").to_string(),
+    })
+}
+pub fn commit_detail(repo: &Repository, oid: git2::Oid) -> Result<CommitDetail, git2::Error> {
+    let commit = repo.find_commit(oid)?;
+    let full_message = String::from_utf8_lossy(commit.message_bytes()).into_owned();
+    let trailers: Vec<(String, String)> = git2::message_trailers_strs(&full_message)
+        .map(|trailers| {
+            trailers
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    // Trailers always form the last paragraph, which is shown separately.
+    let message = match full_message.trim_end().rfind("\n\n") {
+        Some(end) if !trailers.is_empty() => full_message[..end].to_string(),
+        _ => full_message.trim_end().to_string(),
+    };
+    Ok(CommitDetail {
+        id: oid.to_string(),
+        parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+        refs: refs_pointing_at(repo, oid),
+        author: identity(&commit.author()),
+        committer: identity(&commit.committer()),
+        message,
+        trailers,
+    })
+}
+
+fn refs_pointing_at(repo: &Repository, oid: git2::Oid) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Ok(head) = repo.head()
+        && head.peel_to_commit().map(|c| c.id()).ok() == Some(oid)
+    {
+        names.push(match head.shorthand() {
+            Some(branch) if head.is_branch() => format!("HEAD -> {branch}"),
+            _ => "HEAD".to_string(),
         });
     }
-    Ok(commits)
+    let Ok(references) = repo.references() else {
+        return names;
+    };
+    for reference in references.flatten() {
+        if reference.kind() != Some(git2::ReferenceType::Direct)
+            || reference.peel_to_commit().map(|c| c.id()).ok() != Some(oid)
+        {
+            continue;
+        }
+        let Some(name) = reference.shorthand() else {
+            continue;
+        };
+        if reference.is_tag() {
+            names.push(format!("tag: {name}"));
+        } else if !names
+            .iter()
+            .any(|n| n.strip_prefix("HEAD -> ") == Some(name))
+        {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+fn identity(signature: &git2::Signature) -> Identity {
+    Identity {
+        name: signature.name().unwrap_or("Unknown").to_string(),
+        email: signature.email().unwrap_or("").to_string(),
+        date: format_time(signature.when()),
+    }
+}
+
+/// Formats a commit time in its own timezone, e.g. "2024-03-09 14:05:00 +0100".
+fn format_time(time: git2::Time) -> String {
+    let offset = time.offset_minutes();
+    let local = time.seconds() + i64::from(offset) * 60;
+    let (days, secs) = (local.div_euclid(86_400), local.rem_euclid(86_400));
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} {}{:02}{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
 }
 pub fn fetch_status(repo: &Repository) -> Result<Vec<StatusInfo>, git2::Error> {
     let mut opts = git2::StatusOptions::new();
     opts.include_untracked(true).recurse_untracked_dirs(true);
     let statuses = repo.statuses(Some(&mut opts))?;
-    Ok(statuses
-        .iter()
-        .map(|entry| StatusInfo {
-            path: entry.path().unwrap_or("").to_string(),
-            status: entry.status(),
-        })
-        .collect())
+    let staged = Status::INDEX_NEW
+        | Status::INDEX_MODIFIED
+        | Status::INDEX_DELETED
+        | Status::INDEX_RENAMED
+        | Status::INDEX_TYPECHANGE;
+    let unstaged = Status::WT_NEW
+        | Status::WT_MODIFIED
+        | Status::WT_DELETED
+        | Status::WT_RENAMED
+        | Status::WT_TYPECHANGE
+        | Status::CONFLICTED;
+    // All staged entries first, then the unstaged ones, each by path.
+    let mut files = Vec::new();
+    for (side, flags) in [
+        (StatusSide::Staged, staged),
+        (StatusSide::Unstaged, unstaged),
+    ] {
+        files.extend(
+            statuses
+                .iter()
+                .filter(|entry| entry.status().intersects(flags))
+                .map(|entry| StatusInfo {
+                    path: entry.path().unwrap_or("").to_string(),
+                    status: entry.status(),
+                    side,
+                }),
+        );
+    }
+    files.sort_by(|a, b| (a.side, &a.path).cmp(&(b.side, &b.path)));
+    Ok(files)
 }
 
-pub fn stage_toggle(repo: &Repository, file_path: &str) -> Result<(), git2::Error> {
+pub fn branch_status(repo: &Repository) -> BranchStatus {
+    let mut status = BranchStatus::default();
+    let Ok(head) = repo.head() else {
+        // An unborn branch still has a name.
+        if let Ok(reference) = repo.find_reference("HEAD")
+            && let Some(target) = reference.symbolic_target()
+        {
+            status.branch = target.strip_prefix("refs/heads/").map(str::to_string);
+        }
+        return status;
+    };
+    let head_id = head.target();
+    status.head = head_id.map(|id| id.to_string()[..7].to_string());
+    if !head.is_branch() {
+        return status;
+    }
+    status.branch = head.shorthand().map(str::to_string);
+    let upstream = status
+        .branch
+        .as_deref()
+        .and_then(|name| repo.find_branch(name, git2::BranchType::Local).ok())
+        .and_then(|branch| branch.upstream().ok());
+    if let Some(upstream) = upstream {
+        status.upstream = upstream.name().ok().flatten().map(str::to_string);
+        if let (Some(local), Some(remote)) = (head_id, upstream.get().target())
+            && let Ok((ahead, behind)) = repo.graph_ahead_behind(local, remote)
+        {
+            status.ahead = ahead;
+            status.behind = behind;
+        }
+    }
+    status
+}
+pub fn repo_summary(path: &Path) -> Result<RepoSummary, git2::Error> {
+    let repo = Repository::open(path)?;
+    let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(false)
+        .exclude_submodules(true);
+    let dirty = !repo.is_bare() && !repo.statuses(Some(&mut opts))?.is_empty();
+    Ok(RepoSummary {
+        branch: branch_status(&repo),
+        dirty,
+        loaded: true,
+        ..RepoSummary::pending(path)
+    })
+}
+/// Stages the work tree state of `stage`, deletions included, and resets
+/// the entries of `unstage` to HEAD (dropping those HEAD does not have), with
+/// a single index write.
+pub fn update_index(
+    repo: &Repository,
+    stage: &[String],
+    unstage: &[String],
+) -> Result<(), git2::Error> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("Cannot stage in a bare repository"))?;
     let mut index = repo.index()?;
-    let path = Path::new(file_path);
-    let status_entry = repo.status_file(path)?;
+    let head_tree = head_commit(repo)?.map(|head| head.tree()).transpose()?;
+    for path in unstage {
+        reset_index_entry(&mut index, workdir, head_tree.as_ref(), path)?;
+    }
+    for path in stage {
+        if workdir.join(path).symlink_metadata().is_ok() {
+            index.add_path(Path::new(path))?;
+        } else {
+            index.remove_path(Path::new(path))?;
+        }
+    }
+    index.write()
+}
+/// Points the index entry for `path` at its version in `head_tree`, or
+/// drops it when HEAD does not have the file.
+fn reset_index_entry(
+    index: &mut git2::Index,
+    workdir: &Path,
+    head_tree: Option<&git2::Tree>,
+    path: &str,
+) -> Result<(), git2::Error> {
+    let Some(entry) = head_tree.and_then(|tree| tree.get_path(Path::new(path)).ok()) else {
+        return index.remove_path(Path::new(path));
+    };
+    let mode = entry.filemode() as u32;
+    // When the work tree file is the HEAD version, staging it records its
+    // stat data, so that git does not have to rehash it on every status.
+    let is_file = workdir
+        .join(path)
+        .symlink_metadata()
+        .is_ok_and(|meta| !meta.is_dir());
+    if is_file {
+        index.add_path(Path::new(path))?;
+        let staged = index.get_path(Path::new(path), 0);
+        if staged.is_some_and(|staged| staged.id == entry.id() && staged.mode == mode) {
+            return Ok(());
+        }
+    }
+    // Otherwise the file differs from HEAD, and zero stat data makes sure
+    // git compares its content.
+    index.add(&git2::IndexEntry {
+        ctime: git2::IndexTime::new(0, 0),
+        mtime: git2::IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode,
+        uid: 0,
+        gid: 0,
+        file_size: 0,
+        id: entry.id(),
+        flags: path.len().min(0xfff) as u16,
+        flags_extended: 0,
+        path: path.as_bytes().to_vec(),
+    })
+}
+/// Appends `paths` to the top-level `.gitignore`, anchored to the root. A
+/// directory holding nothing tracked and no other untracked files is
+/// ignored as a whole instead of file by file.
+pub fn ignore_paths(repo: &Repository, paths: &[String]) -> Result<(), git2::Error> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("Cannot ignore files in a bare repository"))?;
+    let file = workdir.join(".gitignore");
+    let io_error =
+        |e: std::io::Error| git2::Error::from_str(&format!("Cannot update .gitignore: {e}"));
+    let mut content = match std::fs::read_to_string(&file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(io_error(e)),
+    };
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(true).recurse_untracked_dirs(true);
+    let statuses = repo.statuses(Some(&mut opts))?;
+    // Directories holding tracked files or untracked ones the user did not
+    // pick, which a directory pattern must not cover.
+    let mut occupied = HashSet::new();
+    let index = repo.index()?;
+    let tracked = index
+        .iter()
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned());
+    let others = statuses
+        .iter()
+        .filter(|entry| entry.status().is_wt_new())
+        .filter_map(|entry| entry.path().map(str::to_string))
+        .filter(|path| !paths.contains(path));
+    for path in tracked.chain(others) {
+        for (slash, _) in path.match_indices('/') {
+            occupied.insert(path[..slash].to_string());
+        }
+    }
+    let mut patterns: Vec<String> = Vec::new();
+    for path in paths {
+        let whole_dir = path
+            .match_indices('/')
+            .map(|(slash, _)| &path[..slash])
+            .find(|dir| !occupied.contains(*dir));
+        let pattern = match whole_dir {
+            Some(dir) => format!("/{}/", escape_ignore_pattern(dir)),
+            None => format!("/{}", escape_ignore_pattern(path)),
+        };
+        if !patterns.contains(&pattern) {
+            patterns.push(pattern);
+        }
+    }
+    for pattern in patterns {
+        content.push_str(&pattern);
+        content.push('\n');
+    }
+    std::fs::write(&file, content).map_err(io_error)
+}
 
-    let is_staged = status_entry.is_index_new()
-        || status_entry.is_index_modified()
-        || status_entry.is_index_deleted()
-        || status_entry.is_index_renamed()
-        || status_entry.is_index_typechange();
+/// Quotes the characters `.gitignore` would otherwise treat specially:
+/// glob characters, backslashes and trailing spaces.
+fn escape_ignore_pattern(path: &str) -> String {
+    let kept = path.trim_end_matches(' ');
+    let mut pattern = String::new();
+    for c in kept.chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    for _ in kept.len()..path.len() {
+        pattern.push_str("\\ ");
+    }
+    pattern
+}
+/// The commit HEAD points at, or `None` on an unborn branch.
+fn head_commit(repo: &Repository) -> Result<Option<Commit<'_>>, git2::Error> {
+    match repo.head() {
+        Ok(head) => head.peel_to_commit().map(Some),
+        Err(e)
+            if matches!(
+                e.code(),
+                git2::ErrorCode::UnbornBranch | git2::ErrorCode::NotFound
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
 
-    if is_staged {
-        repo.reset_default(None, [path])?;
+/// Throws away the changes to `paths`. With `from_head` staged changes go
+/// too and index and work tree are reset to HEAD; otherwise the work tree is
+/// checked out from the index. Untracked files, and with `from_head` files
+/// HEAD does not have, are deleted.
+pub fn discard_changes(
+    repo: &Repository,
+    paths: &[String],
+    from_head: bool,
+) -> Result<(), git2::Error> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("Cannot discard changes in a bare repository"))?;
+    let renames = renamed_paths(repo)?;
+    let mut delete = Vec::new();
+    let mut unstage = Vec::new();
+    let mut checkout = Vec::new();
+    for path in paths {
+        // A rename is undone by dropping the new name and restoring the old.
+        if let Some((status, old_path)) = renames.get(path) {
+            if from_head && status.is_index_renamed() {
+                unstage.push(path);
+                delete.push(path);
+                checkout.push(old_path);
+                continue;
+            }
+            if status.is_wt_renamed() {
+                delete.push(path);
+                checkout.push(old_path);
+                continue;
+            }
+        }
+        let status = repo.status_file(Path::new(path))?;
+        if status.is_wt_new() && !status.is_index_new() {
+            delete.push(path);
+        } else if from_head && status.is_index_new() {
+            unstage.push(path);
+            delete.push(path);
+        } else {
+            checkout.push(path);
+        }
+    }
+    // All index changes are made in memory and written once, by the
+    // checkout when there is one.
+    let mut index = repo.index()?;
+    if from_head {
+        let head_tree = head_commit(repo)?.map(|head| head.tree()).transpose()?;
+        for path in &checkout {
+            reset_index_entry(&mut index, workdir, head_tree.as_ref(), path)?;
+        }
+    }
+    for path in unstage {
+        index.remove_path(Path::new(path))?;
+    }
+    if checkout.is_empty() {
+        // An empty path list would check out everything.
+        index.write()?;
     } else {
-        index.add_path(path)?;
+        let mut opts = git2::build::CheckoutBuilder::new();
+        opts.force();
+        for path in &checkout {
+            opts.path(path.as_str());
+        }
+        repo.checkout_index(Some(&mut index), Some(&mut opts))?;
+    }
+    for path in delete {
+        match std::fs::remove_file(workdir.join(path)) {
+            // A staged new file may already be gone from the working tree.
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(git2::Error::from_str(&format!("Cannot delete {path}: {e}")));
+            }
+            _ => {}
+        }
     }
-    index.write()?;
     Ok(())
 }
 
-fn format_diff(diff: &git2::Diff) -> Result<Vec<Spans<'static>>, git2::Error> {
-    let mut lines = Vec::new();
-    diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-        let style = match line.origin() {
-            '+' => Style::default().fg(Color::Green),
-            '-' => Style::default().fg(Color::Red),
-            'H' | 'F' => Style::default().fg(Color::Cyan),
-            _ => Style::default(),
+/// Renamed files by their new path, with the rename's status and old path.
+fn renamed_paths(repo: &Repository) -> Result<HashMap<String, (Status, String)>, git2::Error> {
+    let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(true)
+        .renames_index_to_workdir(true);
+    let statuses = repo.statuses(Some(&mut opts))?;
+    let mut renames = HashMap::new();
+    for entry in statuses.iter() {
+        let status = entry.status();
+        for delta in [entry.head_to_index(), entry.index_to_workdir()]
+            .into_iter()
+            .flatten()
+            .filter(|delta| delta.status() == git2::Delta::Renamed)
+        {
+            if let (Some(old), Some(new)) = (delta.old_file().path(), delta.new_file().path()) {
+                renames.insert(
+                    new.to_string_lossy().into_owned(),
+                    (status, old.to_string_lossy().into_owned()),
+                );
+            }
+        }
+    }
+    Ok(renames)
+}
+
+fn format_diff(diff: &git2::Diff, cancelled: &dyn Fn() -> bool) -> Result<DiffInfo, git2::Error> {
+    let mut info = DiffInfo::default();
+    diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
+        if cancelled() {
+            return false;
+        }
+        let content = String::from_utf8_lossy(line.content()).replace('\t', "    ");
+        let content = content.trim_end_matches(['\n', '\r']);
+        let kind = match line.origin() {
+            'F' => {
+                let path = delta.new_file().path().or(delta.old_file().path());
+                info.files.push(FileDiff {
+                    path: path
+                        .map(|p| p.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    status: status_letter(delta.status()),
+                    header: content.lines().map(str::to_string).collect(),
+                    hunks: Vec::new(),
+                });
+                return true;
+            }
+            'B' => {
+                if let Some(file) = info.files.last_mut() {
+                    file.header.push(content.to_string());
+                }
+                return true;
+            }
+            'H' => {
+                if let Some(file) = info.files.last_mut() {
+                    file.hunks.push(DiffHunk {
+                        header: content.to_string(),
+                        lines: Vec::new(),
+                    });
+                }
+                return true;
+            }
+            '+' => DiffLineKind::Added,
+            '-' => DiffLineKind::Removed,
+            ' ' => DiffLineKind::Context,
+            _ => DiffLineKind::Meta,
         };
-        let content = format!(
-            "{}{}",
-            line.origin(),
-            String::from_utf8_lossy(line.content())
-        );
-        lines.push(Spans::from(Span::styled(content, style)));
+        if let Some(hunk) = info.files.last_mut().and_then(|f| f.hunks.last_mut()) {
+            hunk.lines.push(DiffLine {
+                kind,
+                old_lineno: line.old_lineno(),
+                new_lineno: line.new_lineno(),
+                content: content.trim_start_matches('\n').to_string(),
+                emphasis: Vec::new(),
+                syntax: Vec::new(),
+                markers: None,
+            });
+        }
         true
     })?;
-    Ok(lines)
+    Ok(info)
+}
+fn status_letter(delta: git2::Delta) -> char {
+    match delta {
+        git2::Delta::Added | git2::Delta::Untracked => 'A',
+        git2::Delta::Deleted => 'D',
+        git2::Delta::Renamed => 'R',
+        git2::Delta::Copied => 'C',
+        git2::Delta::Typechange => 'T',
+        _ => 'M',
+    }
+}
+
+fn diff_options(settings: &DiffSettings) -> git2::DiffOptions {
+    let mut opts = git2::DiffOptions::new();
+    opts.context_lines(settings.context_lines)
+        .ignore_whitespace_change(settings.whitespace == Whitespace::IgnoreChange)
+        .ignore_whitespace(settings.whitespace == Whitespace::IgnoreAll)
+        .patience(settings.algorithm == DiffAlgorithm::Patience)
+        .minimal(settings.algorithm == DiffAlgorithm::Minimal);
+    opts
+}
+
+fn find_similar(diff: &mut git2::Diff, settings: &DiffSettings) -> Result<(), git2::Error> {
+    if settings.find_renames {
+        let mut find = git2::DiffFindOptions::new();
+        find.renames(true)
+            .copies(true)
+            .ignore_whitespace(settings.whitespace != Whitespace::Show);
+        diff.find_similar(Some(&mut find))?;
+    }
+    Ok(())
 }
+/// The changes a commit made relative to its parent number `parent`
+/// (counting from 0), or to the empty tree for a root commit.
 pub fn get_commit_diff(
     repo: &Repository,
-    commit: &CommitInfo,
-) -> Result<Vec<Spans<'static>>, git2::Error> {
-    let commit_oid = git2::Oid::from_str(&commit.id)?;
+    commit_oid: git2::Oid,
+    parent: usize,
+    settings: &DiffSettings,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<DiffInfo, git2::Error> {
     let commit = repo.find_commit(commit_oid)?;
-    let parent_commit = if commit.parent_count() > 0 {
-        Some(commit.parent(0)?)
-    } else {
-        None
+    // A root commit is compared with the empty tree.
+    let parent_tree = match commit.parent_count() {
+        0 if parent == 0 => None,
+        count if parent < count => Some(commit.parent(parent)?.tree()?),
+        count => {
+            return Err(git2::Error::from_str(&format!(
+                "Commit {} has no parent {} (it has {count})",
+                commit.id(),
+                parent + 1
+            )));
+        }
     };
     let tree = commit.tree()?;
-    let parent_tree = parent_commit.as_ref().and_then(|p| p.tree().ok());
-    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
-    format_diff(&diff)
+    diff_trees(repo, parent_tree.as_ref(), &tree, settings, cancelled)
+}
+/// The changes needed to go from commit `from` to commit `to`.
+pub fn get_range_diff(
+    repo: &Repository,
+    from: git2::Oid,
+    to: git2::Oid,
+    settings: &DiffSettings,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<DiffInfo, git2::Error> {
+    let old_tree = repo.find_commit(from)?.tree()?;
+    let new_tree = repo.find_commit(to)?.tree()?;
+    diff_trees(repo, Some(&old_tree), &new_tree, settings, cancelled)
 }
+/// A condensed combined diff of a merge commit against all of its parents,
+/// like `git show --cc`. Only files that differ from every parent are shown.
+pub fn get_combined_diff(
+    repo: &Repository,
+    commit_oid: git2::Oid,
+    settings: &DiffSettings,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<DiffInfo, git2::Error> {
+    let commit = repo.find_commit(commit_oid)?;
+    let tree = commit.tree()?;
+    let parent_trees = commit
+        .parents()
+        .map(|parent| parent.tree())
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut paths: Option<Vec<String>> = None;
+    for parent_tree in &parent_trees {
+        let mut opts = diff_options(settings);
+        let diff = repo.diff_tree_to_tree(Some(parent_tree), Some(&tree), Some(&mut opts))?;
+        let mut changed = Vec::new();
+        for (index, delta) in diff.deltas().enumerate() {
+            // Ignoring whitespace can leave a modified file without hunks;
+            // binary files have no patch and always count.
+            let differs = settings.whitespace == Whitespace::Show
+                || delta.status() != git2::Delta::Modified
+                || git2::Patch::from_diff(&diff, index)?.is_none_or(|patch| patch.num_hunks() > 0);
+            if let Some(path) = delta.new_file().path().or(delta.old_file().path())
+                && differs
+            {
+                changed.push(path.to_string_lossy().into_owned());
+            }
+        }
+        paths = Some(match paths {
+            None => changed,
+            Some(paths) => paths.into_iter().filter(|p| changed.contains(p)).collect(),
+        });
+    }
+    let algorithm = match settings.algorithm {
+        DiffAlgorithm::Patience => similar::Algorithm::Patience,
+        DiffAlgorithm::Myers | DiffAlgorithm::Minimal => similar::Algorithm::Myers,
+    };
+    let mut info = DiffInfo::default();
+    for path in paths.unwrap_or_default() {
+        if cancelled() {
+            return Err(git2::Error::from_str("cancelled"));
+        }
+        let mut versions = Vec::with_capacity(parent_trees.len());
+        let mut binary = false;
+        for tree in parent_trees.iter().chain([&tree]) {
+            let blob = match tree.get_path(Path::new(&path)) {
+                Ok(entry) => Some(entry.to_object(repo)?.peel_to_blob()?),
+                Err(_) => None,
+            };
+            binary |= blob.as_ref().is_some_and(git2::Blob::is_binary);
+            versions.push(
+                blob.map(|blob| String::from_utf8_lossy(blob.content()).into_owned())
+                    .unwrap_or_default(),
+            );
+        }
+        if binary {
+            info.files.push(FileDiff {
+                path: path.clone(),
+                status: 'M',
+                header: vec![
+                    format!("diff --cc {path}"),
+                    "Binary files differ".to_string(),
+                ],
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+        let result = versions.pop().unwrap_or_default();
+        info.files.push(combined_diff::combine_file(
+            &path,
+            &versions,
+            &result,
+            algorithm,
+            settings.context_lines as usize,
+            settings.whitespace,
+        ));
+    }
+    Ok(info)
+}
+/// The changes from a commit's tree to the working tree (through the index,
+/// like `git diff <commit>`), or only to the index (`git diff --cached`).
+pub fn get_workspace_diff(
+    repo: &Repository,
+    commit: git2::Oid,
+    index_only: bool,
+    settings: &DiffSettings,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<DiffInfo, git2::Error> {
+    let tree = repo.find_commit(commit)?.tree()?;
+    let mut opts = diff_options(settings);
+    let mut diff = if index_only {
+        repo.diff_tree_to_index(Some(&tree), None, Some(&mut opts))?
+    } else {
+        repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut opts))?
+    };
+    find_similar(&mut diff, settings)?;
+    format_diff(&diff, cancelled)
+}
+fn diff_trees(
+    repo: &Repository,
+    old_tree: Option<&git2::Tree>,
+    new_tree: &git2::Tree,
+    settings: &DiffSettings,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<DiffInfo, git2::Error> {
+    let mut diff =
+        repo.diff_tree_to_tree(old_tree, Some(new_tree), Some(&mut diff_options(settings)))?;
+    find_similar(&mut diff, settings)?;
+    format_diff(&diff, cancelled)
+}
+/// Resolves a revspec such as `HEAD~3`, `v1.0`, `main..feature` or
+/// `main...feature` to the commits to diff: `(None, to)` for a single
+/// revision, which is compared against its first parent.
+pub fn resolve_revspec(
+    repo: &Repository,
+    spec: &str,
+) -> Result<(Option<git2::Oid>, git2::Oid), git2::Error> {
+    let revspec = repo.revparse(spec)?;
+    let commit_id = |object: Option<&git2::Object>| -> Result<git2::Oid, git2::Error> {
+        let object = object.ok_or_else(|| git2::Error::from_str("Incomplete revision range"))?;
+        Ok(object.peel_to_commit()?.id())
+    };
+    if revspec.mode().contains(git2::RevparseMode::SINGLE) {
+        return Ok((None, commit_id(revspec.from())?));
+    }
+    let from = commit_id(revspec.from())?;
+    let to = commit_id(revspec.to())?;
+    if revspec.mode().contains(git2::RevparseMode::MERGE_BASE) {
+        return Ok((Some(repo.merge_base(from, to)?), to));
+    }
+    Ok((Some(from), to))
+}
+/// The staged changes to `path` (index against HEAD), or its unstaged ones
+/// (work tree against the index), where an untracked file shows in full.
 pub fn get_file_diff(
     repo: &Repository,
-    file: &StatusInfo,
-) -> Result<Vec<Spans<'static>>, git2::Error> {
-    let diff = repo.diff_tree_to_workdir_with_index(
-        None,
-        Some(git2::DiffOptions::new().pathspec(&file.path)),
-    )?;
-    format_diff(&diff)
+    path: &str,
+    side: StatusSide,
+    settings: &DiffSettings,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<DiffInfo, git2::Error> {
+    let mut opts = diff_options(settings);
+    // A directory covers everything below it, and no path the whole side.
+    // The path is literal: `*` or `[` in a file name is not a pattern.
+    if !path.is_empty() {
+        opts.pathspec(path).disable_pathspec_match(true);
+    }
+    let mut diff = match side {
+        StatusSide::Staged => {
+            let tree = head_commit(repo)?.map(|head| head.tree()).transpose()?;
+            repo.diff_tree_to_index(tree.as_ref(), None, Some(&mut opts))?
+        }
+        StatusSide::Unstaged => {
+            opts.include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .show_untracked_content(true);
+            repo.diff_index_to_workdir(None, Some(&mut opts))?
+        }
+    };
+    find_similar(&mut diff, settings)?;
+    format_diff(&diff, cancelled)
+}
+/// The patches touching `path` in the history of `from`, newest first, like
+/// `git log -p -- path`. Each commit's file header starts with a line naming
+/// the commit.
+pub fn file_history(
+    repo: &Repository,
+    from: git2::Oid,
+    path: &str,
+    settings: &DiffSettings,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<DiffInfo, git2::Error> {
+    const LIMIT: usize = 100;
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(from)?;
+    revwalk.set_sorting(Sort::TIME)?;
+    let entry_id = |commit: &Commit| {
+        let tree = commit.tree().ok()?;
+        tree.get_path(Path::new(path)).ok().map(|entry| entry.id())
+    };
+    let mut info = DiffInfo::default();
+    let mut commits = 0;
+    for oid in revwalk {
+        if cancelled() {
+            return Err(git2::Error::from_str("cancelled"));
+        }
+        let commit = repo.find_commit(oid?)?;
+        let parent = commit.parents().next();
+        if entry_id(&commit) == parent.as_ref().and_then(entry_id) {
+            continue;
+        }
+        if commits == LIMIT {
+            info.preamble
+                .push(format!("Showing the {LIMIT} most recent commits touching {path}").into());
+            break;
+        }
+        commits += 1;
+        let parent_tree = parent.as_ref().map(Commit::tree).transpose()?;
+        let diff = repo.diff_tree_to_tree(
+            parent_tree.as_ref(),
+            Some(&commit.tree()?),
+            Some(
+                diff_options(settings)
+                    .pathspec(path)
+                    .disable_pathspec_match(true),
+            ),
+        )?;
+        let author = commit.author();
+        let title = format!(
+            "commit {} {} {} {}",
+            &commit.id().to_string()[..7],
+            &format_time(author.when())[..10],
+            author.name().unwrap_or("Unknown"),
+            commit.summary().unwrap_or("")
+        );
+        for mut file in format_diff(&diff, cancelled)?.files {
+            file.header.insert(0, title.clone());
+            info.files.push(file);
+        }
+    }
+    if info.files.is_empty() {
+        return Ok(DiffInfo::message(format!("No history for {path}")));
+    }
+    Ok(info)
+}
+
+/// Blames `path` as of `commit`, or as of its first parent if the commit
+/// deleted the file.
+pub fn blame_file(
+    repo: &Repository,
+    commit: git2::Oid,
+    path: &str,
+) -> Result<Vec<BlameLine>, git2::Error> {
+    let mut commit = repo.find_commit(commit)?;
+    if commit.tree()?.get_path(Path::new(path)).is_err() && commit.parent_count() > 0 {
+        commit = commit.parent(0)?;
+    }
+    let blob = commit
+        .tree()?
+        .get_path(Path::new(path))?
+        .to_object(repo)?
+        .peel_to_blob()?;
+    if blob.is_binary() {
+        return Err(git2::Error::from_str("Cannot blame a binary file"));
+    }
+    let mut opts = git2::BlameOptions::new();
+    opts.newest_commit(commit.id());
+    let blame = repo.blame_file(Path::new(path), Some(&mut opts))?;
+    let text = String::from_utf8_lossy(blob.content());
+    Ok(text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            let hunk = blame.get_line(index + 1);
+            let signature = hunk.as_ref().map(|hunk| hunk.final_signature());
+            BlameLine {
+                lineno: index + 1,
+                commit: hunk
+                    .as_ref()
+                    .map(|hunk| hunk.final_commit_id().to_string()[..7].to_string())
+                    .unwrap_or_default(),
+                author: signature
+                    .as_ref()
+                    .and_then(|s| s.name().map(str::to_string))
+                    .unwrap_or_default(),
+                date: signature
+                    .as_ref()
+                    .map(|s| format_time(s.when())[..10].to_string())
+                    .unwrap_or_default(),
+                content: line.replace('\t', "    "),
+            }
+        })
+        .collect())
+}
+/// The blob currently staged for `path`, if any.
+pub fn index_entry_id(repo: &Repository, path: &str) -> Option<git2::Oid> {
+    let index = repo.index().ok()?;
+    index.get_path(Path::new(path), 0).map(|entry| entry.id)
 }
 pub fn create_commit(repo: &Repository, message: &str) -> Result<(), git2::Error> {
     let mut index = repo.index()?;
     let oid = index.write_tree()?;
     let tree = repo.find_tree(oid)?;
     let signature = repo.signature()?;
-    let head = repo.head()?;
-    if let Some(target) = head.target() {
-        let parent_commit = repo.find_commit(target)?;
-        repo.commit(
-            Some("HEAD"),
-            &signature,
-            &signature,
-            message,
-            &tree,
-            &[&parent_commit],
-        )?;
-    } else {
-        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &[])?;
+    let parent_commit = match repo.head() {
+        Ok(head) => match head.target() {
+            Some(target) => Some(repo.find_commit(target)?),
+            None => None,
+        },
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(e),
+    };
+    let parents: Vec<&Commit> = parent_commit.iter().collect();
+
+    match Signer::for_commits(repo)? {
+        Some(signer) => {
+            let buffer =
+                repo.commit_create_buffer(&signature, &signature, message, &tree, &parents)?;
+            let content = buffer
+                .as_str()
+                .ok_or_else(|| git2::Error::from_str("Commit buffer is not valid UTF-8"))?;
+            let gpgsig = signer.sign(content)?;
+            let commit_oid = repo.commit_signed(content, &gpgsig, Some("gpgsig"))?;
+            let summary = message.lines().next().unwrap_or("");
+            advance_head(repo, commit_oid, &format!("commit: {summary}"))?;
+        }
+        None => {
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )?;
+        }
+    }
+    Ok(())
+}
+fn advance_head(repo: &Repository, oid: git2::Oid, log_message: &str) -> Result<(), git2::Error> {
+    let head = repo.find_reference("HEAD")?;
+    match head.symbolic_target() {
+        Some(branch) => {
+            repo.reference(branch, oid, true, log_message)?;
+        }
+        None => repo.set_head_detached(oid)?,
+    }
+    Ok(())
+}
+pub fn create_tag(
+    repo: &Repository,
+    commit: &CommitInfo,
+    name: &str,
+    message: &str,
+) -> Result<(), git2::Error> {
+    let ref_name = format!("refs/tags/{name}");
+    if !git2::Reference::is_valid_name(&ref_name) {
+        return Err(git2::Error::from_str(&format!(
+            "'{name}' is not a valid tag name"
+        )));
+    }
+    let target = repo.find_commit(git2::Oid::from_str(&commit.id)?)?;
+    let tagger = repo.signature()?;
+    let mut message = message.trim_end().to_string();
+    message.push('\n');
+
+    match Signer::for_tags(repo)? {
+        Some(signer) => {
+            if repo.find_reference(&ref_name).is_ok() {
+                return Err(git2::Error::from_str(&format!(
+                    "Tag '{name}' already exists"
+                )));
+            }
+            let mut payload = format!(
+                "object {}\ntype commit\ntag {name}\ntagger {}\n\n{message}",
+                target.id(),
+                format_raw_signature(&tagger)
+            );
+            payload.push_str(&signer.sign(&payload)?);
+            let tag_oid = repo
+                .odb()?
+                .write(git2::ObjectType::Tag, payload.as_bytes())?;
+            repo.reference(&ref_name, tag_oid, false, "tag: signed")?;
+        }
+        None => {
+            repo.tag(name, target.as_object(), &tagger, &message, false)?;
+        }
     }
     Ok(())
 }
+fn format_raw_signature(signature: &git2::Signature) -> String {
+    let when = signature.when();
+    let offset = when.offset_minutes();
+    format!(
+        "{} <{}> {} {}{:02}{:02}",
+        signature.name().unwrap_or(""),
+        signature.email().unwrap_or(""),
+        when.seconds(),
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
 pub fn push_to_remote(repo: &Repository) -> Result<(), git2::Error> {
     let mut remote = repo.find_remote("origin")?;
     let mut callbacks = git2::RemoteCallbacks::new();
@@ -140,3 +1007,224 @@ pub fn push_to_remote(repo: &Repository) -> Result<(), git2::Error> {
     let refspec = head.name().unwrap();
     remote.push(&[refspec], Some(&mut push_options))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A repository in a fresh temporary directory, deleted on drop.
+    struct TempRepo {
+        dir: PathBuf,
+        repo: Repository,
+    }
+
+    impl TempRepo {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("termigit-test-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            let repo = Repository::init(&dir).unwrap();
+            TempRepo { dir, repo }
+        }
+
+        /// Commits a tree holding `file` with `content` on top of `parents`,
+        /// moving `branch` to the new commit.
+        fn commit(&self, branch: &str, parents: &[git2::Oid], content: &str) -> git2::Oid {
+            self.commit_files(branch, parents, &[("file", content)], content)
+        }
+
+        /// Commits a tree of top-level `files`, given as (name, content).
+        fn commit_files(
+            &self,
+            branch: &str,
+            parents: &[git2::Oid],
+            files: &[(&str, &str)],
+            message: &str,
+        ) -> git2::Oid {
+            let mut tree = self.repo.treebuilder(None).unwrap();
+            for (name, content) in files {
+                let blob = self.repo.blob(content.as_bytes()).unwrap();
+                tree.insert(name, blob, 0o100644).unwrap();
+            }
+            let tree = self.repo.find_tree(tree.write().unwrap()).unwrap();
+            let parents: Vec<Commit> = parents
+                .iter()
+                .map(|id| self.repo.find_commit(*id).unwrap())
+                .collect();
+            let signature =
+                git2::Signature::new("Test", "test@example.com", &git2::Time::new(0, 0)).unwrap();
+            self.repo
+                .commit(
+                    Some(&format!("refs/heads/{branch}")),
+                    &signature,
+                    &signature,
+                    message,
+                    &tree,
+                    &parents.iter().collect::<Vec<_>>(),
+                )
+                .unwrap()
+        }
+    }
+
+    impl TempRepo {
+        /// Commits `files` on `main` and checks them out.
+        fn checkout(&self, files: &[(&str, &str)]) {
+            self.commit_files("main", &[], files, "base");
+            self.repo.set_head("refs/heads/main").unwrap();
+            self.repo
+                .checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+                .unwrap();
+        }
+
+        fn write(&self, path: &str, content: &str) {
+            std::fs::write(self.dir.join(path), content).unwrap();
+        }
+
+        fn stage(&self, path: &str) {
+            let mut index = self.repo.index().unwrap();
+            index.add_path(Path::new(path)).unwrap();
+            index.write().unwrap();
+        }
+
+        fn status(&self, path: &str) -> Status {
+            self.repo.status_file(Path::new(path)).unwrap()
+        }
+    }
+
+    impl Drop for TempRepo {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn resolve_revspec_handles_single_revisions_and_ranges() {
+        let temp = TempRepo::new("revspec");
+        let base = temp.commit("main", &[], "base");
+        let feature = temp.commit("feature", &[base], "feature");
+        let main = temp.commit("main", &[base], "main");
+        let resolve = |spec| resolve_revspec(&temp.repo, spec).unwrap();
+
+        assert_eq!(resolve("main"), (None, main));
+        assert_eq!(resolve("main~1"), (None, base));
+        assert_eq!(resolve("main..feature"), (Some(main), feature));
+        // The three-dot form starts from the merge base.
+        assert_eq!(resolve("main...feature"), (Some(base), feature));
+    }
+
+    #[test]
+    fn resolve_revspec_rejects_unknown_revisions() {
+        let temp = TempRepo::new("revspec-error");
+        temp.commit("main", &[], "base");
+
+        assert!(resolve_revspec(&temp.repo, "missing").is_err());
+        assert!(resolve_revspec(&temp.repo, "main..missing").is_err());
+        assert!(resolve_revspec(&temp.repo, "main~5").is_err());
+    }
+
+    #[test]
+    fn format_time_uses_the_commit_timezone() {
+        let at = |seconds, offset| format_time(git2::Time::new(seconds, offset));
+        assert_eq!(at(0, 0), "1970-01-01 00:00:00 +0000");
+        assert_eq!(at(0, -300), "1969-12-31 19:00:00 -0500");
+        assert_eq!(at(0, 330), "1970-01-01 05:30:00 +0530");
+        assert_eq!(at(0, -210), "1969-12-31 20:30:00 -0330");
+    }
+
+    #[test]
+    fn format_time_handles_leap_days() {
+        let at = |seconds, offset| format_time(git2::Time::new(seconds, offset));
+        assert_eq!(at(1_709_249_400, 0), "2024-02-29 23:30:00 +0000");
+        assert_eq!(at(1_709_249_400, 60), "2024-03-01 00:30:00 +0100");
+        assert_eq!(at(951_868_799, 0), "2000-02-29 23:59:59 +0000");
+        assert_eq!(at(951_868_800, 0), "2000-03-01 00:00:00 +0000");
+    }
+
+    #[test]
+    fn file_history_matches_the_path_literally() {
+        let temp = TempRepo::new("history");
+        let first = temp.commit_files(
+            "main",
+            &[],
+            &[("a[1].rs", "one"), ("a1.rs", "one")],
+            "first",
+        );
+        let second = temp.commit_files(
+            "main",
+            &[first],
+            &[("a[1].rs", "one"), ("a1.rs", "two")],
+            "second",
+        );
+        let history = file_history(
+            &temp.repo,
+            second,
+            "a[1].rs",
+            &DiffSettings::default(),
+            &|| false,
+        )
+        .unwrap();
+        let paths: Vec<&str> = history.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["a[1].rs"]);
+    }
+
+    #[test]
+    fn unstage_keeps_stat_data_only_for_files_matching_head() {
+        let temp = TempRepo::new("unstage");
+        temp.checkout(&[("same", "one"), ("edited", "one")]);
+        for path in ["same", "edited"] {
+            temp.write(path, "two");
+            temp.stage(path);
+        }
+        temp.write("same", "one");
+        temp.write("edited", "three");
+
+        update_index(&temp.repo, &[], &["same".into(), "edited".into()]).unwrap();
+
+        let index = temp.repo.index().unwrap();
+        let mtime = |path: &str| index.get_path(Path::new(path), 0).unwrap().mtime.seconds();
+        assert_ne!(mtime("same"), 0);
+        assert_eq!(mtime("edited"), 0);
+        assert!(temp.status("same").is_empty());
+        assert_eq!(temp.status("edited"), Status::WT_MODIFIED);
+    }
+
+    #[test]
+    fn discard_from_head_restores_files_in_one_pass() {
+        let temp = TempRepo::new("discard");
+        temp.checkout(&[("both", "one"), ("staged", "one")]);
+        temp.write("both", "two");
+        temp.stage("both");
+        temp.write("both", "three");
+        temp.write("staged", "two");
+        temp.stage("staged");
+        // Added to the index, then deleted from the work tree.
+        temp.write("added", "new");
+        temp.stage("added");
+        std::fs::remove_file(temp.dir.join("added")).unwrap();
+        temp.write("untracked", "new");
+
+        let paths = ["both", "staged", "added", "untracked"].map(String::from);
+        discard_changes(&temp.repo, &paths, true).unwrap();
+
+        let mut opts = git2::StatusOptions::new();
+        opts.include_untracked(true);
+        assert!(temp.repo.statuses(Some(&mut opts)).unwrap().is_empty());
+        let content = std::fs::read_to_string(temp.dir.join("both")).unwrap();
+        assert_eq!(content, "one");
+    }
+
+    #[test]
+    fn discard_unstaged_keeps_the_index() {
+        let temp = TempRepo::new("discard-unstaged");
+        temp.checkout(&[("file", "one")]);
+        temp.write("file", "two");
+        temp.stage("file");
+        temp.write("file", "three");
+
+        discard_changes(&temp.repo, &["file".into()], false).unwrap();
+
+        assert_eq!(temp.status("file"), Status::INDEX_MODIFIED);
+        let content = std::fs::read_to_string(temp.dir.join("file")).unwrap();
+        assert_eq!(content, "two");
+    }
+}
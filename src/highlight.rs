@@ -0,0 +1,92 @@
+// src/highlight.rs
+
+use crate::types::diff_info::{DiffInfo, DiffLineKind};
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use tui::style::Color;
+
+const THEME: &str = "base16-eighties.dark";
+/// Diffs larger than this are shown without syntax colors.
+const MAX_LINES: usize = 20_000;
+const MAX_LINE_LEN: usize = 2000;
+
+fn assets() -> &'static (SyntaxSet, Theme) {
+    static ASSETS: OnceLock<(SyntaxSet, Theme)> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        let syntaxes = SyntaxSet::load_defaults_nonewlines();
+        let mut themes = ThemeSet::load_defaults();
+        let theme = themes.themes.remove(THEME).unwrap_or_default();
+        (syntaxes, theme)
+    })
+}
+
+/// Fills in `DiffLine::syntax` using the language implied by each file's
+/// name. The old and new sides are highlighted with separate parser states so
+/// that removed and added lines are each read in their own context.
+pub fn highlight(info: &mut DiffInfo, cancelled: &dyn Fn() -> bool) {
+    let total: usize = info
+        .files
+        .iter()
+        .flat_map(|file| &file.hunks)
+        .map(|hunk| hunk.lines.len())
+        .sum();
+    if total > MAX_LINES {
+        return;
+    }
+    let (syntaxes, theme) = assets();
+
+    for file in &mut info.files {
+        let Some(syntax) = find_syntax(syntaxes, &file.path) else {
+            continue;
+        };
+        let mut old_side = HighlightLines::new(syntax, theme);
+        let mut new_side = HighlightLines::new(syntax, theme);
+        for line in file.hunks.iter_mut().flat_map(|hunk| &mut hunk.lines) {
+            if cancelled() {
+                return;
+            }
+            if line.content.len() > MAX_LINE_LEN {
+                continue;
+            }
+            let highlighter = match line.kind {
+                DiffLineKind::Removed => &mut old_side,
+                DiffLineKind::Added => &mut new_side,
+                DiffLineKind::Context => {
+                    let _ = old_side.highlight_line(&line.content, syntaxes);
+                    &mut new_side
+                }
+                DiffLineKind::Meta => continue,
+            };
+            let Ok(regions) = highlighter.highlight_line(&line.content, syntaxes) else {
+                continue;
+            };
+            let mut start = 0;
+            line.syntax = regions
+                .iter()
+                .map(|(style, text)| {
+                    let region = (
+                        start,
+                        Color::Rgb(style.foreground.r, style.foreground.g, style.foreground.b),
+                    );
+                    start += text.len();
+                    region
+                })
+                .collect();
+        }
+    }
+}
+
+fn find_syntax<'a>(syntaxes: &'a SyntaxSet, path: &str) -> Option<&'a SyntaxReference> {
+    let path = Path::new(path);
+    let by_extension = path
+        .extension()
+        .and_then(|ext| syntaxes.find_syntax_by_extension(&ext.to_string_lossy()));
+    let syntax = by_extension.or_else(|| {
+        path.file_name()
+            .and_then(|name| syntaxes.find_syntax_by_extension(&name.to_string_lossy()))
+    })?;
+    (syntax.name != "Plain Text").then_some(syntax)
+}
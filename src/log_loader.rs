@@ -0,0 +1,105 @@
+// src/log_loader.rs
+
+use crate::git;
+use crate::types::commit_info::CommitInfo;
+use git2::Repository;
+use std::path::PathBuf;
+use std::sync::mpsc as std_mpsc;
+use std::thread;
+use tokio::sync::mpsc;
+
+const PAGE_SIZE: usize = 250;
+
+pub struct LogPage {
+    pub commits: Vec<CommitInfo>,
+    /// The revwalk is exhausted; no further pages will follow.
+    pub done: bool,
+}
+
+/// Streams the commit log in pages from a background thread. The first page
+/// is produced right away; each further page only after `request_more`.
+/// Dropping the loader stops the walk.
+pub struct LogLoader {
+    more_sender: std_mpsc::Sender<()>,
+    pub receiver: mpsc::UnboundedReceiver<Result<LogPage, git2::Error>>,
+    pub done: bool,
+    requested: bool,
+}
+
+impl LogLoader {
+    pub fn start(repo_path: PathBuf) -> Self {
+        let (more_tx, more_rx) = std_mpsc::channel();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        thread::spawn(move || {
+            let repo = match Repository::open(repo_path) {
+                Ok(repo) => repo,
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            };
+            let mut revwalk = match git::log_walk(&repo) {
+                Ok(Some(revwalk)) => revwalk,
+                Ok(None) => {
+                    let _ = tx.send(Ok(LogPage {
+                        commits: Vec::new(),
+                        done: true,
+                    }));
+                    return;
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            };
+            loop {
+                let mut commits = Vec::with_capacity(PAGE_SIZE);
+                let mut done = true;
+                for oid in revwalk.by_ref() {
+                    match oid.and_then(|oid| git::commit_info(&repo, oid)) {
+                        Ok(info) => commits.push(info),
+                        Err(e) => {
+                            let _ = tx.send(Err(e));
+                            return;
+                        }
+                    }
+                    if commits.len() == PAGE_SIZE {
+                        done = false;
+                        break;
+                    }
+                }
+                if tx.send(Ok(LogPage { commits, done })).is_err() || done {
+                    return;
+                }
+                if more_rx.recv().is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            more_sender: more_tx,
+            receiver: rx,
+            done: false,
+            requested: true,
+        }
+    }
+
+    /// Asks for the next page unless one is already on its way.
+    pub fn request_more(&mut self) {
+        if !self.done && !self.requested {
+            self.requested = self.more_sender.send(()).is_ok();
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.requested && !self.done
+    }
+
+    /// Records that a page (or an error) arrived from the background thread.
+    pub fn page_received(&mut self, done: bool) {
+        self.requested = false;
+        self.done = done;
+    }
+}
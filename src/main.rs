@@ -1,34 +1,67 @@
 // src/main.rs
 
 mod app;
+mod combined_diff;
+mod confirm;
+mod diff_view;
+mod diff_worker;
 mod git;
+mod highlight;
+mod log_loader;
+mod notifications;
+mod signing;
+mod status_tree;
+mod terminal;
 pub mod types;
 mod ui;
+mod watcher;
+mod word_diff;
 
-use crate::app::{App, AppMode};
+use crate::app::App;
+use crate::types::repo_summary::RepoSummary;
 use crate::ui::draw;
-use crossterm::{
-    event::{self, Event, KeyEventKind},
-    execute,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
-};
-use std::io;
+use crate::watcher::{RepoChange, RepoWatcher};
+use crossterm::event::{self, Event, KeyEventKind};
+use git2::Repository;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 use tui::Terminal;
 use tui::backend::CrosstermBackend; // CORRECTED: Removed unused time::Duration
 
+const USAGE: &str = "Usage: git_tui [PATH]... [--scan DIR]...
+
+Opens the git repository containing each PATH (default: the current
+directory). --scan adds every repository directly inside DIR. With more than
+one repository, press R to switch between them.";
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
+    let Some(args) = parse_args() else {
+        return Ok(());
+    };
+    let workspace = match open_workspace(args) {
+        Ok(Some(workspace)) => workspace,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    let repo = Repository::open(&workspace[0])?;
+    let mut app = App::new(repo)?;
+    app.workspace = workspace;
+
+    terminal::enter()?;
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new()?;
     let res = run_app(&mut terminal, &mut app).await;
 
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal::leave()?;
     terminal.show_cursor()?;
 
     if let Err(err) = res {
@@ -39,24 +72,148 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[derive(Default)]
+struct Args {
+    paths: Vec<PathBuf>,
+    scan: Vec<PathBuf>,
+}
+
+/// Parses the command line, or returns `None` after printing the usage for
+/// `--help`.
+fn parse_args() -> Option<Args> {
+    let mut args = Args::default();
+    let mut raw = std::env::args_os().skip(1);
+    while let Some(arg) = raw.next() {
+        match arg.to_str() {
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                return None;
+            }
+            Some("-s" | "--scan") => match raw.next() {
+                Some(dir) => args.scan.push(PathBuf::from(dir)),
+                None => {
+                    eprintln!("{USAGE}");
+                    std::process::exit(2);
+                }
+            },
+            _ => args.paths.push(PathBuf::from(arg)),
+        }
+    }
+    if args.paths.is_empty() && args.scan.is_empty() {
+        args.paths.push(PathBuf::from("."));
+    }
+    Some(args)
+}
+
+/// Resolves the arguments to the root directories of the repositories to
+/// work with, the first of which is opened. `None` means a lone path had no
+/// repository and the user declined to create one.
+fn open_workspace(args: Args) -> Result<Option<Vec<PathBuf>>, String> {
+    let root = |repo: &Repository| repo.workdir().unwrap_or(repo.path()).to_path_buf();
+    let mut workspace: Vec<PathBuf> = Vec::new();
+    if let [path] = args.paths.as_slice()
+        && args.scan.is_empty()
+    {
+        return Ok(open_repository(path)?.map(|repo| vec![root(&repo)]));
+    }
+    for path in &args.paths {
+        let repo = Repository::discover(path).map_err(|e| {
+            format!(
+                "Cannot open a repository at {}: {}",
+                path.display(),
+                e.message()
+            )
+        })?;
+        workspace.push(root(&repo));
+    }
+    for dir in &args.scan {
+        let entries =
+            std::fs::read_dir(dir).map_err(|e| format!("Cannot scan {}: {e}", dir.display()))?;
+        let mut found: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+            .filter_map(|entry| Repository::open(entry.path()).ok())
+            .map(|repo| root(&repo))
+            .collect();
+        found.sort();
+        workspace.extend(found);
+    }
+    let mut seen = HashSet::new();
+    workspace.retain(|path| seen.insert(path.clone()));
+    if workspace.is_empty() {
+        return Err("No repositories found".to_string());
+    }
+    Ok(Some(workspace))
+}
+
+/// Finds the repository containing `path`. When there is none, offers to
+/// create one there; `None` means the user declined.
+fn open_repository(path: &Path) -> Result<Option<Repository>, String> {
+    match Repository::discover(path) {
+        Ok(repo) => return Ok(Some(repo)),
+        Err(e) if e.code() == git2::ErrorCode::NotFound && path.is_dir() => {}
+        Err(e) => {
+            return Err(format!(
+                "Cannot open a repository at {}: {}",
+                path.display(),
+                e.message()
+            ));
+        }
+    }
+    print!(
+        "{} is not inside a git repository. Initialize one there? [y/N] ",
+        path.display()
+    );
+    let mut answer = String::new();
+    io::stdout()
+        .flush()
+        .and_then(|()| io::stdin().read_line(&mut answer))
+        .map_err(|e| e.to_string())?;
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        return Ok(None);
+    }
+    Repository::init(path).map(Some).map_err(|e| e.to_string())
+}
+
 async fn run_app<B: tui::backend::Backend>(
     terminal: &mut Terminal<B>,
     app: &mut App,
 ) -> io::Result<()> {
+    let mut events = spawn_event_reader();
     loop {
+        if terminal::take_needs_clear() {
+            terminal.clear()?;
+        }
         terminal.draw(|f| draw(f, app))?;
 
         tokio::select! {
-            result = tokio::task::spawn_blocking(event::read) => {
-                if let Ok(Ok(Event::Key(key))) = result {
-                    if key.kind == KeyEventKind::Press {
+            event = events.recv() => {
+                match event {
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
                         app.handle_key_event(key);
                     }
+                    Some(Ok(Event::Mouse(mouse))) => app.handle_mouse_event(mouse),
+                    Some(Err(err)) => return Err(err),
+                    None => return Ok(()),
+                    _ => {}
                 }
             }
-            Some(msg) = app.push_feedback_receiver.recv() => {
-                app.mode = AppMode::Pushing(msg);
+            Some(result) = app.push_feedback_receiver.recv() => {
+                app.handle_push_feedback(result);
+            }
+            Some(response) = app.diff_worker.receiver.recv() => {
+                app.handle_diff_response(response);
+            }
+            Some(page) = app.log_loader.receiver.recv() => {
+                app.handle_log_page(page);
+            }
+            Some(change) = next_repo_change(&mut app.watcher) => {
+                app.handle_repo_change(change);
+            }
+            Some((index, summary)) = next_repo_summary(&mut app.summary_receiver) => {
+                app.handle_repo_summary(index, summary);
             }
+            () = toast_expiry(app.notifications.next_expiry()) => {}
         }
 
         if app.should_quit {
@@ -64,3 +221,62 @@ async fn run_app<B: tui::backend::Backend>(
         }
     }
 }
+
+/// Reads terminal events on a dedicated thread so that no key press is lost
+/// when another branch of the `select!` in `run_app` wins. It pauses while
+/// the terminal is suspended for a child process.
+fn spawn_event_reader() -> mpsc::UnboundedReceiver<io::Result<Event>> {
+    const IDLE: Duration = Duration::from_millis(50);
+    let (tx, rx) = mpsc::unbounded_channel();
+    thread::spawn(move || {
+        loop {
+            if terminal::is_suspended() {
+                thread::sleep(IDLE);
+                continue;
+            }
+            match event::poll(IDLE) {
+                Ok(false) => continue,
+                Ok(true) if terminal::is_suspended() => continue,
+                Ok(true) => {}
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            }
+            let event = event::read();
+            let failed = event.is_err();
+            if tx.send(event).is_err() || failed {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+async fn next_repo_change(watcher: &mut Option<RepoWatcher>) -> Option<RepoChange> {
+    match watcher {
+        Some(watcher) => watcher.receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn next_repo_summary(
+    receiver: &mut Option<mpsc::UnboundedReceiver<(usize, RepoSummary)>>,
+) -> Option<(usize, RepoSummary)> {
+    if let Some(rx) = receiver {
+        match rx.recv().await {
+            Some(summary) => return Some(summary),
+            // All summaries are in.
+            None => *receiver = None,
+        }
+    }
+    std::future::pending().await
+}
+
+/// Wakes the loop when a toast is due to go away, so it is redrawn without it.
+async fn toast_expiry(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
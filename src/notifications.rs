@@ -0,0 +1,106 @@
+// src/notifications.rs
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Entries kept for the message log; older ones are dropped.
+const LOG_LIMIT: usize = 200;
+/// Toasts shown at once; older ones make way for newer ones early.
+const MAX_TOASTS: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Routine results such as staging a file: logged and shown in the
+    /// status bar, but not as a toast.
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// How long the toast stays up; problems linger so they can be read.
+    fn lifetime(self) -> Duration {
+        match self {
+            Severity::Info => Duration::ZERO,
+            Severity::Success => Duration::from_secs(3),
+            Severity::Warning => Duration::from_secs(6),
+            Severity::Error => Duration::from_secs(10),
+        }
+    }
+}
+
+pub struct Notification {
+    pub severity: Severity,
+    pub message: String,
+    pub created: Instant,
+    expires: Instant,
+}
+
+/// Every message shown to the user, newest last, and which of them are
+/// still up as toasts.
+#[derive(Default)]
+pub struct Notifications {
+    log: VecDeque<Notification>,
+}
+
+impl Notifications {
+    pub fn push(&mut self, severity: Severity, message: String) {
+        let created = Instant::now();
+        if self.log.len() == LOG_LIMIT {
+            self.log.pop_front();
+        }
+        self.log.push_back(Notification {
+            severity,
+            message,
+            created,
+            expires: created + severity.lifetime(),
+        });
+        let mut live = 0;
+        for n in self.log.iter_mut().rev().filter(|n| n.expires > created) {
+            live += 1;
+            if live > MAX_TOASTS {
+                n.expires = created;
+            }
+        }
+    }
+
+    /// Adds `other`'s entries after this log's own.
+    pub fn append(&mut self, other: Notifications) {
+        self.log.extend(other.log);
+        while self.log.len() > LOG_LIMIT {
+            self.log.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&Notification> {
+        self.log.back()
+    }
+
+    /// Newest first.
+    pub fn log(&self) -> impl Iterator<Item = &Notification> {
+        self.log.iter().rev()
+    }
+
+    pub fn len(&self) -> usize {
+        self.log.len()
+    }
+
+    /// The toasts to draw, newest first.
+    pub fn toasts(&self) -> impl Iterator<Item = &Notification> {
+        let now = Instant::now();
+        self.log.iter().rev().filter(move |n| n.expires > now)
+    }
+
+    /// When the next toast goes away, so the screen can be redrawn then.
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.toasts().map(|n| n.expires).min()
+    }
+
+    pub fn dismiss_toasts(&mut self) {
+        let now = Instant::now();
+        for n in &mut self.log {
+            n.expires = n.expires.min(now);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// src/signing.rs
+
+use crate::terminal;
+use git2::{Config, Repository};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+enum SignFormat {
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+/// Signs commit and tag payloads the way `git` does, by piping them through
+/// `gpg`, `gpgsm` or `ssh-keygen -Y sign` as selected by `gpg.format`.
+pub struct Signer {
+    format: SignFormat,
+    program: String,
+    key: String,
+}
+
+impl Signer {
+    /// Builds a signer for commits, or `None` when `commit.gpgsign` is off.
+    pub fn for_commits(repo: &Repository) -> Result<Option<Self>, git2::Error> {
+        let config = repo.config()?;
+        if !config.get_bool("commit.gpgsign").unwrap_or(false) {
+            return Ok(None);
+        }
+        Self::from_config(repo, &config).map(Some)
+    }
+
+    /// Builds a signer for annotated tags, or `None` when `tag.gpgsign` is
+    /// off. As in git, `commit.gpgsign` has no say over tags.
+    pub fn for_tags(repo: &Repository) -> Result<Option<Self>, git2::Error> {
+        let config = repo.config()?;
+        if !config.get_bool("tag.gpgsign").unwrap_or(false) {
+            return Ok(None);
+        }
+        Self::from_config(repo, &config).map(Some)
+    }
+
+    fn from_config(repo: &Repository, config: &Config) -> Result<Self, git2::Error> {
+        let format = match config.get_string("gpg.format").as_deref() {
+            Ok("ssh") => SignFormat::Ssh,
+            Ok("x509") => SignFormat::X509,
+            Ok("openpgp") | Err(_) => SignFormat::OpenPgp,
+            Ok(other) => {
+                return Err(git2::Error::from_str(&format!(
+                    "Unsupported gpg.format '{other}'"
+                )));
+            }
+        };
+        let program = match format {
+            SignFormat::OpenPgp => config
+                .get_string("gpg.openpgp.program")
+                .or_else(|_| config.get_string("gpg.program"))
+                .unwrap_or_else(|_| "gpg".to_string()),
+            SignFormat::X509 => config
+                .get_string("gpg.x509.program")
+                .unwrap_or_else(|_| "gpgsm".to_string()),
+            SignFormat::Ssh => config
+                .get_string("gpg.ssh.program")
+                .unwrap_or_else(|_| "ssh-keygen".to_string()),
+        };
+        let key = match config.get_string("user.signingkey") {
+            Ok(key) => key,
+            Err(_) if matches!(format, SignFormat::Ssh) => {
+                return Err(git2::Error::from_str(
+                    "SSH signing requires user.signingkey to be set",
+                ));
+            }
+            Err(_) => {
+                let signature = repo.signature()?;
+                format!(
+                    "{} <{}>",
+                    signature.name().unwrap_or(""),
+                    signature.email().unwrap_or("")
+                )
+            }
+        };
+        Ok(Self {
+            format,
+            program,
+            key,
+        })
+    }
+
+    /// Returns the detached, armored signature for `payload`.
+    pub fn sign(&self, payload: &str) -> Result<String, git2::Error> {
+        match self.format {
+            SignFormat::OpenPgp | SignFormat::X509 => {
+                let mut command = Command::new(&self.program);
+                command.args(["--status-fd=2", "-bsau", &self.key]);
+                run_signer(command, payload)
+            }
+            SignFormat::Ssh => self.sign_ssh(payload),
+        }
+    }
+
+    fn sign_ssh(&self, payload: &str) -> Result<String, git2::Error> {
+        let literal = self.key.strip_prefix("key::").unwrap_or(&self.key);
+        let mut command = Command::new(&self.program);
+        command.args(["-Y", "sign", "-n", "git", "-f"]);
+
+        if literal.starts_with("ssh-")
+            || literal.starts_with("ecdsa-")
+            || literal.starts_with("sk-")
+        {
+            // A literal public key: the private half has to come from the agent.
+            let key_file = write_key_file(literal)
+                .map_err(|e| git2::Error::from_str(&format!("Could not write signing key: {e}")))?;
+            command.arg(&key_file).arg("-U");
+            let result = run_signer(command, payload);
+            let removed = fs::remove_file(&key_file).map_err(|e| {
+                git2::Error::from_str(&format!("Could not remove {}: {e}", key_file.display()))
+            });
+            result.and_then(|signature| removed.map(|()| signature))
+        } else {
+            command.arg(expand_home(literal));
+            run_signer(command, payload)
+        }
+    }
+}
+
+/// Writes a literal public key to a fresh file only the user can read.
+/// The file is created exclusively so an existing file or symlink at the
+/// same path is never written through.
+fn write_key_file(key: &str) -> io::Result<PathBuf> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let mut attempt = 0;
+    loop {
+        let path = env::temp_dir().join(format!(
+            "termigit-signingkey-{}-{nanos}-{attempt}.pub",
+            std::process::id()
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        match options.open(&path) {
+            Ok(mut file) => {
+                if let Err(e) = file.write_all(key.as_bytes()) {
+                    let _ = fs::remove_file(&path);
+                    return Err(e);
+                }
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 16 => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Runs the signer with the terminal handed over, as `gpg` or `ssh-keygen`
+/// may ask for a passphrase on it.
+fn run_signer(command: Command, payload: &str) -> Result<String, git2::Error> {
+    terminal::suspended(|| sign_with(command, payload)).map_err(|e| {
+        git2::Error::from_str(&format!("Could not hand the terminal to the signer: {e}"))
+    })?
+}
+
+fn sign_with(mut command: Command, payload: &str) -> Result<String, git2::Error> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| git2::Error::from_str(&format!("Could not run {program}: {e}")))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(payload.as_bytes())
+            .map_err(|e| git2::Error::from_str(&format!("Could not write to {program}: {e}")))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| git2::Error::from_str(&format!("{program} failed: {e}")))?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .filter(|line| !line.starts_with("[GNUPG:]"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        return Err(git2::Error::from_str(&format!(
+            "Signing with {program} failed: {}",
+            reason.trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
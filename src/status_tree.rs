@@ -0,0 +1,266 @@
+// src/status_tree.rs
+
+use crate::types::status_info::{StatusInfo, StatusSide};
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// A row of the Status panel. Every row stands for a contiguous range of
+/// `App.status_files`, so actions on a heading or directory apply to all the
+/// files below it.
+pub enum StatusRow {
+    Header {
+        side: StatusSide,
+        files: Range<usize>,
+    },
+    /// A directory of the tree view, by its path from the repository root.
+    Dir {
+        side: StatusSide,
+        path: String,
+        depth: usize,
+        files: Range<usize>,
+        collapsed: bool,
+    },
+    File {
+        index: usize,
+        depth: usize,
+    },
+}
+
+impl StatusRow {
+    pub fn files(&self) -> Range<usize> {
+        match self {
+            StatusRow::Header { files, .. } | StatusRow::Dir { files, .. } => files.clone(),
+            StatusRow::File { index, .. } => *index..*index + 1,
+        }
+    }
+}
+
+/// Lays out both sections, staged first, as flat path lists or as trees of
+/// directories. `files` must be sorted by side, then path, so that each
+/// directory's files are contiguous; `collapsed` names the directories whose
+/// contents are hidden.
+pub fn build_rows(
+    files: &[StatusInfo],
+    tree: bool,
+    collapsed: &HashSet<(StatusSide, String)>,
+) -> Vec<StatusRow> {
+    let split = files
+        .iter()
+        .position(|s| s.side == StatusSide::Unstaged)
+        .unwrap_or(files.len());
+    let mut rows = Vec::new();
+    for (side, range) in [
+        (StatusSide::Staged, 0..split),
+        (StatusSide::Unstaged, split..files.len()),
+    ] {
+        rows.push(StatusRow::Header {
+            side,
+            files: range.clone(),
+        });
+        if tree {
+            add_tree(&mut rows, files, side, range, collapsed);
+        } else {
+            rows.extend(range.map(|index| StatusRow::File { index, depth: 0 }));
+        }
+    }
+    rows
+}
+
+fn add_tree(
+    rows: &mut Vec<StatusRow>,
+    files: &[StatusInfo],
+    side: StatusSide,
+    range: Range<usize>,
+    collapsed: &HashSet<(StatusSide, String)>,
+) {
+    // The directories enclosing the current file, outermost first, as the
+    // row drawn for each (`None` inside a collapsed directory) and the
+    // directory's path with a trailing slash.
+    let mut open: Vec<(Option<usize>, String)> = Vec::new();
+    let close = |rows: &mut Vec<StatusRow>, row: Option<usize>, end: usize| {
+        if let Some(StatusRow::Dir { files, .. }) = row.and_then(|row| rows.get_mut(row)) {
+            files.end = end;
+        }
+    };
+    for index in range.clone() {
+        let path = files[index].path.as_str();
+        while let Some((row, prefix)) = open.last() {
+            if path.starts_with(prefix.as_str()) {
+                break;
+            }
+            close(rows, *row, index);
+            open.pop();
+        }
+        let mut start = open.last().map_or(0, |(_, prefix)| prefix.len());
+        while let Some(slash) = path[start..].find('/') {
+            let dir = &path[..start + slash];
+            let hidden = is_hidden(rows, &open);
+            let row = (!hidden).then(|| {
+                rows.push(StatusRow::Dir {
+                    side,
+                    path: dir.to_string(),
+                    depth: open.len(),
+                    files: index..index,
+                    collapsed: collapsed.contains(&(side, dir.to_string())),
+                });
+                rows.len() - 1
+            });
+            open.push((row, format!("{dir}/")));
+            start += slash + 1;
+        }
+        if !is_hidden(rows, &open) {
+            rows.push(StatusRow::File {
+                index,
+                depth: open.len(),
+            });
+        }
+    }
+    for (row, _) in open {
+        close(rows, row, range.end);
+    }
+}
+
+/// Whether anything inside the innermost open directory is out of sight.
+fn is_hidden(rows: &[StatusRow], open: &[(Option<usize>, String)]) -> bool {
+    open.last().is_some_and(|(row, _)| match row {
+        Some(row) => matches!(
+            rows[*row],
+            StatusRow::Dir {
+                collapsed: true,
+                ..
+            }
+        ),
+        None => true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Status;
+
+    fn files(staged: &[&str], unstaged: &[&str]) -> Vec<StatusInfo> {
+        let side = |paths: &[&str], side, status| {
+            paths
+                .iter()
+                .map(|path| StatusInfo {
+                    path: path.to_string(),
+                    status,
+                    side,
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut files = side(staged, StatusSide::Staged, Status::INDEX_MODIFIED);
+        files.extend(side(unstaged, StatusSide::Unstaged, Status::WT_MODIFIED));
+        files
+    }
+
+    /// Each row as `<depth><kind> <path or index> <files>`, e.g. `1d a/b 0..2`.
+    fn layout(rows: &[StatusRow]) -> Vec<String> {
+        rows.iter()
+            .map(|row| match row {
+                StatusRow::Header { side, files } => format!("h {side:?} {files:?}"),
+                StatusRow::Dir {
+                    path,
+                    depth,
+                    files,
+                    collapsed,
+                    ..
+                } => {
+                    let kind = if *collapsed { '+' } else { 'd' };
+                    format!("{depth}{kind} {path} {files:?}")
+                }
+                StatusRow::File { index, depth } => format!("{depth}f {index}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flat_lists_every_file_under_its_side() {
+        let files = files(&["a/x"], &["b", "c/d/e"]);
+        let rows = build_rows(&files, false, &HashSet::new());
+        assert_eq!(
+            layout(&rows),
+            ["h Staged 0..1", "0f 0", "h Unstaged 1..3", "0f 1", "0f 2"]
+        );
+    }
+
+    #[test]
+    fn nested_directories_cover_their_files() {
+        let files = files(&[], &["a/b/c/x", "a/b/y", "a/z", "top"]);
+        let rows = build_rows(&files, true, &HashSet::new());
+        assert_eq!(
+            layout(&rows),
+            [
+                "h Staged 0..0",
+                "h Unstaged 0..4",
+                "0d a 0..3",
+                "1d a/b 0..2",
+                "2d a/b/c 0..1",
+                "3f 0",
+                "2f 1",
+                "1f 2",
+                "0f 3"
+            ]
+        );
+    }
+
+    #[test]
+    fn sibling_with_a_common_prefix_is_not_nested() {
+        // '.' sorts before '/', so "a.b/" comes first and shares a prefix
+        // with "a" without being inside it.
+        let files = files(&[], &["a.b/y", "a/x"]);
+        let rows = build_rows(&files, true, &HashSet::new());
+        assert_eq!(
+            layout(&rows),
+            [
+                "h Staged 0..0",
+                "h Unstaged 0..2",
+                "0d a.b 0..1",
+                "1f 0",
+                "0d a 1..2",
+                "1f 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn collapsed_directory_hides_nested_children() {
+        let files = files(&[], &["a/b/x", "a/b/y", "a/z", "c"]);
+        let collapsed = HashSet::from([(StatusSide::Unstaged, "a".to_string())]);
+        let rows = build_rows(&files, true, &collapsed);
+        assert_eq!(
+            layout(&rows),
+            ["h Staged 0..0", "h Unstaged 0..4", "0+ a 0..3", "0f 3"]
+        );
+    }
+
+    #[test]
+    fn collapsed_directory_only_folds_its_own_side() {
+        let files = files(&["a/x"], &["a/x"]);
+        let collapsed = HashSet::from([(StatusSide::Unstaged, "a".to_string())]);
+        let rows = build_rows(&files, true, &collapsed);
+        assert_eq!(
+            layout(&rows),
+            [
+                "h Staged 0..1",
+                "0d a 0..1",
+                "1f 0",
+                "h Unstaged 1..2",
+                "0+ a 1..2"
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_sections_keep_their_headers() {
+        let rows = build_rows(&[], true, &HashSet::new());
+        assert_eq!(layout(&rows), ["h Staged 0..0", "h Unstaged 0..0"]);
+        let files = files(&["a/x"], &[]);
+        let rows = build_rows(&files, true, &HashSet::new());
+        assert_eq!(
+            layout(&rows),
+            ["h Staged 0..1", "0d a 0..1", "1f 0", "h Unstaged 1..1"]
+        );
+    }
+}
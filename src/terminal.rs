@@ -0,0 +1,48 @@
+// src/terminal.rs
+
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::execute;
+use crossterm::terminal::{
+    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set while a child process owns the terminal; the event reader stays off
+/// it so that key presses reach the child.
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+/// Set when the screen was given away and must be drawn again in full.
+static NEEDS_CLEAR: AtomicBool = AtomicBool::new(false);
+
+/// Switches to raw mode on the alternate screen, with mouse reporting.
+pub fn enter() -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)
+}
+
+/// Restores the terminal as it was before `enter`.
+pub fn leave() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)
+}
+
+/// Runs `f` with the terminal back in its normal state, for programs that
+/// may prompt on it, such as a `gpg` pinentry asking for a passphrase.
+pub fn suspended<T>(f: impl FnOnce() -> T) -> io::Result<T> {
+    SUSPENDED.store(true, Ordering::SeqCst);
+    let result = leave().map(|()| f());
+    let entered = enter();
+    NEEDS_CLEAR.store(true, Ordering::SeqCst);
+    SUSPENDED.store(false, Ordering::SeqCst);
+    let result = result?;
+    entered.map(|()| result)
+}
+
+pub fn is_suspended() -> bool {
+    SUSPENDED.load(Ordering::SeqCst)
+}
+
+/// Whether the screen has to be cleared before the next draw; resets the flag.
+pub fn take_needs_clear() -> bool {
+    NEEDS_CLEAR.swap(false, Ordering::SeqCst)
+}
@@ -0,0 +1,10 @@
+// src/types/blame_info.rs
+
+/// One line of a file, annotated with the commit that last changed it.
+pub struct BlameLine {
+    pub lineno: usize,
+    pub commit: String,
+    pub author: String,
+    pub date: String,
+    pub content: String,
+}
@@ -6,3 +6,25 @@ pub struct CommitInfo {
     pub message: String,
     pub author: String,
 }
+
+/// A commit author or committer, with the date already formatted.
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+    pub date: String,
+}
+
+/// Everything shown above a commit's diff.
+#[derive(Clone, Debug)]
+pub struct CommitDetail {
+    pub id: String,
+    pub parents: Vec<String>,
+    /// Short names of the refs pointing at this commit, `HEAD` first.
+    pub refs: Vec<String>,
+    pub author: Identity,
+    pub committer: Identity,
+    /// The full message without its trailer block.
+    pub message: String,
+    pub trailers: Vec<(String, String)>,
+}
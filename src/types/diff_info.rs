@@ -0,0 +1,114 @@
+// src/types/diff_info.rs
+
+use std::ops::Range;
+use tui::style::Color;
+use tui::text::Spans;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+    /// Markers such as "\ No newline at end of file".
+    Meta,
+}
+
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+    pub content: String,
+    /// Byte ranges of `content` that differ from the paired line on the other
+    /// side of the change.
+    pub emphasis: Vec<Range<usize>>,
+    /// Syntax colors as (byte offset, color) pairs, each running up to the
+    /// next offset. Empty when the language is unknown.
+    pub syntax: Vec<(usize, Color)>,
+    /// The per-parent +/- columns of a combined merge diff, shown in place
+    /// of the single origin marker.
+    pub markers: Option<String>,
+}
+
+pub struct DiffHunk {
+    pub header: String,
+    pub lines: Vec<DiffLine>,
+}
+
+impl DiffHunk {
+    /// Aligns the lines into (old, new) pairs: context lines sit on both
+    /// sides, and each run of removals is matched line by line with the run
+    /// of additions that follows it.
+    pub fn paired_lines(&self) -> Vec<(Option<usize>, Option<usize>)> {
+        let mut pairs = Vec::new();
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        let flush = |pairs: &mut Vec<_>, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+            for i in 0..removed.len().max(added.len()) {
+                pairs.push((removed.get(i).copied(), added.get(i).copied()));
+            }
+            removed.clear();
+            added.clear();
+        };
+        for (index, line) in self.lines.iter().enumerate() {
+            match line.kind {
+                DiffLineKind::Removed => {
+                    if !added.is_empty() {
+                        flush(&mut pairs, &mut removed, &mut added);
+                    }
+                    removed.push(index);
+                }
+                DiffLineKind::Added => added.push(index),
+                DiffLineKind::Context => {
+                    flush(&mut pairs, &mut removed, &mut added);
+                    pairs.push((Some(index), Some(index)));
+                }
+                DiffLineKind::Meta => {
+                    flush(&mut pairs, &mut removed, &mut added);
+                    pairs.push((Some(index), None));
+                }
+            }
+        }
+        flush(&mut pairs, &mut removed, &mut added);
+        pairs
+    }
+}
+
+pub struct FileDiff {
+    /// The new path, or the old one for deletions.
+    pub path: String,
+    /// A `git status`-style letter: A, M, D, R, C or T.
+    pub status: char,
+    /// The `diff --git`, `index`, `---` and `+++` lines, or a binary notice.
+    pub header: Vec<String>,
+    pub hunks: Vec<DiffHunk>,
+}
+
+impl FileDiff {
+    /// Added and removed line counts.
+    pub fn line_counts(&self) -> (usize, usize) {
+        let lines = self.hunks.iter().flat_map(|hunk| &hunk.lines);
+        lines.fold((0, 0), |(added, removed), line| match line.kind {
+            DiffLineKind::Added => (added + 1, removed),
+            DiffLineKind::Removed => (added, removed + 1),
+            _ => (added, removed),
+        })
+    }
+}
+
+/// A diff that keeps its file and hunk structure so that it can be laid out
+/// either unified or side by side.
+#[derive(Default)]
+pub struct DiffInfo {
+    /// Lines shown above the first file, such as errors.
+    pub preamble: Vec<Spans<'static>>,
+    pub files: Vec<FileDiff>,
+}
+
+impl DiffInfo {
+    pub fn message(text: String) -> Self {
+        Self {
+            preamble: vec![Spans::from(text)],
+            ..Self::default()
+        }
+    }
+}
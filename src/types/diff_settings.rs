@@ -0,0 +1,81 @@
+// src/types/diff_settings.rs
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Whitespace {
+    Show,
+    IgnoreChange,
+    IgnoreAll,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiffAlgorithm {
+    Myers,
+    Patience,
+    Minimal,
+}
+
+/// Options applied to every diff. Part of the diff cache key, so toggling
+/// them back and forth reuses earlier results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DiffSettings {
+    pub whitespace: Whitespace,
+    pub context_lines: u32,
+    pub algorithm: DiffAlgorithm,
+    /// Detect renames and copies with `find_similar`.
+    pub find_renames: bool,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        Self {
+            whitespace: Whitespace::Show,
+            context_lines: 3,
+            algorithm: DiffAlgorithm::Myers,
+            find_renames: false,
+        }
+    }
+}
+
+impl Whitespace {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Show => Self::IgnoreChange,
+            Self::IgnoreChange => Self::IgnoreAll,
+            Self::IgnoreAll => Self::Show,
+        }
+    }
+}
+
+impl DiffAlgorithm {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Myers => Self::Patience,
+            Self::Patience => Self::Minimal,
+            Self::Minimal => Self::Myers,
+        }
+    }
+}
+
+impl DiffSettings {
+    /// A compact summary for the diff panel title, e.g. "ctx 3 · patience · renames".
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("ctx {}", self.context_lines)];
+        parts.push(
+            match self.algorithm {
+                DiffAlgorithm::Myers => "myers",
+                DiffAlgorithm::Patience => "patience",
+                DiffAlgorithm::Minimal => "minimal",
+            }
+            .to_string(),
+        );
+        match self.whitespace {
+            Whitespace::Show => {}
+            Whitespace::IgnoreChange => parts.push("ignore ws change".to_string()),
+            Whitespace::IgnoreAll => parts.push("ignore all ws".to_string()),
+        }
+        if self.find_renames {
+            parts.push("renames".to_string());
+        }
+        parts.join(" · ")
+    }
+}
@@ -1,4 +1,8 @@
 // src/types/mod.rs
 
+pub mod blame_info;
 pub mod commit_info;
+pub mod diff_info;
+pub mod diff_settings;
+pub mod repo_summary;
 pub mod status_info;
@@ -0,0 +1,42 @@
+// src/types/repo_summary.rs
+
+use std::path::{Path, PathBuf};
+
+/// Where HEAD points and how it compares with its upstream.
+#[derive(Clone, Debug, Default)]
+pub struct BranchStatus {
+    /// The branch name, or `None` when HEAD is detached or unborn.
+    pub branch: Option<String>,
+    /// The short id HEAD points at, if any.
+    pub head: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+/// One entry of the repository switcher.
+pub struct RepoSummary {
+    pub path: PathBuf,
+    pub name: String,
+    pub branch: BranchStatus,
+    /// Uncommitted changes, including untracked files.
+    pub dirty: bool,
+    /// False until the branch and dirty state have been read.
+    pub loaded: bool,
+}
+
+impl RepoSummary {
+    /// A row for the repository at `path` whose state is not yet known.
+    pub fn pending(path: &Path) -> Self {
+        RepoSummary {
+            path: path.to_path_buf(),
+            name: path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            branch: BranchStatus::default(),
+            dirty: false,
+            loaded: false,
+        }
+    }
+}
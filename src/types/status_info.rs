@@ -2,7 +2,39 @@
 
 use git2::Status;
 
+/// Which half of the Status panel an entry is listed in.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum StatusSide {
+    /// Index against HEAD.
+    Staged,
+    /// Work tree against the index, including untracked files.
+    Unstaged,
+}
+
+/// A file with changes on one side. A file changed on both sides has an
+/// entry for each.
 pub struct StatusInfo {
     pub path: String,
     pub status: Status,
+    pub side: StatusSide,
+}
+
+impl StatusInfo {
+    /// The `git status --short` letter for this side of the file.
+    pub fn code(&self) -> char {
+        let s = self.status;
+        match self.side {
+            StatusSide::Staged if s.is_index_new() => 'A',
+            StatusSide::Staged if s.is_index_deleted() => 'D',
+            StatusSide::Staged if s.is_index_renamed() => 'R',
+            StatusSide::Staged if s.is_index_typechange() => 'T',
+            StatusSide::Staged => 'M',
+            StatusSide::Unstaged if s.is_conflicted() => 'U',
+            StatusSide::Unstaged if s.is_wt_new() => '?',
+            StatusSide::Unstaged if s.is_wt_deleted() => 'D',
+            StatusSide::Unstaged if s.is_wt_renamed() => 'R',
+            StatusSide::Unstaged if s.is_wt_typechange() => 'T',
+            StatusSide::Unstaged => 'M',
+        }
+    }
 }
@@ -1,36 +1,60 @@
 // src/ui.rs
 
-use crate::app::{ActivePanel, App, AppMode};
-use git2::Status;
+use crate::app::{ActivePanel, App, AppMode, FileView};
+use crate::confirm::Confirmation;
+use crate::diff_view::DiffLayout;
+use crate::diff_worker::MergeView;
+use crate::notifications::Severity;
+use crate::status_tree::StatusRow;
+use crate::types::repo_summary::BranchStatus;
+use crate::types::status_info::{StatusInfo, StatusSide};
+use git2::RepositoryState;
 use tui::{
     Frame,
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Spans, Text},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 // CORRECTED: Removed unused `tui_input::Input`
 // The `EventHandler` trait is used in app.rs, not here.
 
 // ... (The rest of the file is unchanged) ...
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let screen = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(f.size());
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
-        .split(f.size());
+        .split(screen[0]);
     let top_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
         .split(main_chunks[0]);
-    draw_commits_panel(f, app, top_chunks[0]);
+    app.commits_area = top_chunks[0];
+    if app.commit_files.is_some() {
+        draw_commit_files_panel(f, app, top_chunks[0]);
+    } else {
+        draw_commits_panel(f, app, top_chunks[0]);
+    }
     draw_status_panel_with_help(f, app, top_chunks[1]);
     draw_diff_panel(f, app, main_chunks[1]);
+    draw_status_bar(f, app, screen[1]);
     match &app.mode {
         AppMode::CommitInput => draw_commit_popup(f, app),
+        AppMode::TagInput => draw_tag_popup(f, app),
+        AppMode::RevspecInput => draw_revspec_popup(f, app),
+        AppMode::RepoSwitcher => draw_repo_switcher(f, app),
+        AppMode::MessageLog => draw_message_log(f, app),
+        AppMode::Confirm(confirmation) => draw_confirm_popup(f, confirmation),
         AppMode::Pushing(msg) => draw_push_popup(f, msg),
         AppMode::Normal => {}
     }
+    draw_toasts(f, app);
 }
 fn draw_commits_panel<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let is_active = matches!(app.active_panel, ActivePanel::Commits);
@@ -44,14 +68,31 @@ fn draw_commits_panel<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     } else {
         Color::DarkGray
     };
+    let height = area.height.saturating_sub(2) as usize;
+    let selected = app.commit_list_state.selected();
+    app.commit_list_offset =
+        scroll_offset(app.commit_list_offset, selected, height, app.commits.len());
+    let marked = app.marked_commit.map(|oid| oid.to_string());
     let items: Vec<ListItem> = app
         .commits
         .iter()
+        .skip(app.commit_list_offset)
+        .take(height)
         .map(|c| {
+            let id_style = if marked.as_deref() == Some(c.id.as_str()) {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Yellow)
+            };
             ListItem::new(vec![Spans::from(vec![
-                Span::styled(&c.id[..7], Style::default().fg(Color::Yellow)),
+                Span::styled(&c.id[..7], id_style),
+                Span::raw(" "),
+                Span::raw(c.message.as_str()),
                 Span::raw(" "),
-                Span::raw(c.message.clone()),
+                Span::styled(c.author.as_str(), Style::default().fg(Color::DarkGray)),
             ])])
         })
         .collect();
@@ -59,7 +100,72 @@ fn draw_commits_panel<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Commits")
+                .title(commits_title(app))
+                .border_style(border_style),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(highlight_bg)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+    let mut state = window_state(selected, app.commit_list_offset);
+    f.render_stateful_widget(list, area, &mut state);
+}
+fn draw_commit_files_panel<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let is_active = matches!(app.active_panel, ActivePanel::Commits);
+    let border_style = if is_active {
+        Style::default().fg(Color::White)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    };
+    let highlight_bg = if is_active {
+        Color::LightBlue
+    } else {
+        Color::DarkGray
+    };
+    let Some(commit_files) = &mut app.commit_files else {
+        return;
+    };
+    let height = area.height.saturating_sub(2) as usize;
+    let selected = commit_files.list_state.selected();
+    commit_files.list_offset = scroll_offset(
+        commit_files.list_offset,
+        selected,
+        height,
+        commit_files.files().len(),
+    );
+    let short_id = &commit_files.commit.to_string()[..7];
+    let title = match &commit_files.diff {
+        Some(diff) => format!("Files in {short_id} ({})", diff.files.len()),
+        None => format!("Files in {short_id} (loading...)"),
+    };
+    let items: Vec<ListItem> = commit_files
+        .files()
+        .iter()
+        .skip(commit_files.list_offset)
+        .take(height)
+        .map(|file| {
+            let color = match file.status {
+                'A' => Color::Green,
+                'D' => Color::Red,
+                'R' | 'C' => Color::Cyan,
+                _ => Color::Yellow,
+            };
+            let (added, removed) = file.line_counts();
+            ListItem::new(Spans::from(vec![
+                Span::styled(format!("{} ", file.status), Style::default().fg(color)),
+                Span::raw(file.path.as_str()),
+                Span::styled(format!(" +{added}"), Style::default().fg(Color::Green)),
+                Span::styled(format!(" -{removed}"), Style::default().fg(Color::Red)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
                 .border_style(border_style),
         )
         .highlight_style(
@@ -68,15 +174,45 @@ fn draw_commits_panel<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
                 .add_modifier(Modifier::BOLD),
         )
         .highlight_symbol(">> ");
-    f.render_stateful_widget(list, area, &mut app.commit_list_state);
+    let mut state = window_state(selected, commit_files.list_offset);
+    f.render_stateful_widget(list, area, &mut state);
+}
+/// Returns the first row to show so that `selected` stays inside a window of
+/// `height` rows, moving the previous `offset` as little as possible.
+fn scroll_offset(offset: usize, selected: Option<usize>, height: usize, len: usize) -> usize {
+    let offset = offset.min(len.saturating_sub(height));
+    match selected {
+        Some(selected) if selected < offset => selected,
+        Some(selected) if height > 0 && selected >= offset + height => selected + 1 - height,
+        _ => offset,
+    }
+}
+/// A `ListState` for a list built from only the rows starting at `offset`.
+fn window_state(selected: Option<usize>, offset: usize) -> ListState {
+    let mut state = ListState::default();
+    state.select(selected.map(|i| i - offset));
+    state
+}
+fn commits_title(app: &App) -> String {
+    if app.log_loader.done {
+        format!("Commits ({})", app.commits.len())
+    } else if app.log_loader.is_loading() {
+        format!("Commits ({}+, loading...)", app.commits.len())
+    } else {
+        format!("Commits ({}+)", app.commits.len())
+    }
 }
 fn draw_status_panel_with_help<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let help = help_lines(help_text(app), area.width.saturating_sub(2) as usize);
+    // Keep a few rows of the Status panel however long the help gets.
+    let height = (help.len() as u16 + 2).min(area.height.saturating_sub(4).max(3));
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(3)])
+        .constraints([Constraint::Min(0), Constraint::Length(height)])
         .split(area);
+    app.status_area = chunks[0];
     draw_status_panel(f, app, chunks[0]);
-    draw_help(f, app, chunks[1]);
+    draw_help(f, help, chunks[1]);
 }
 fn draw_status_panel<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let is_active = matches!(app.active_panel, ActivePanel::Status);
@@ -90,23 +226,88 @@ fn draw_status_panel<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     } else {
         Color::DarkGray
     };
+    let height = area.height.saturating_sub(2) as usize;
+    let selected = app.status_list_state.selected();
+    app.status_list_offset = scroll_offset(
+        app.status_list_offset,
+        selected,
+        height,
+        app.status_rows.len(),
+    );
     let items: Vec<ListItem> = app
-        .status_files
+        .status_rows
         .iter()
-        .map(|s| {
-            let (prefix, style) = get_status_style(s.status);
-            ListItem::new(Spans::from(vec![
-                Span::styled(prefix, style),
-                Span::raw(" "),
-                Span::raw(s.path.clone()),
-            ]))
+        .skip(app.status_list_offset)
+        .take(height)
+        .map(|row| {
+            let files = &app.status_files[row.files()];
+            let marked = !files.is_empty()
+                && files
+                    .iter()
+                    .all(|s| app.status_marks.contains(&(s.path.clone(), s.side)));
+            let marker = if marked {
+                Span::styled("* ", Style::default().fg(Color::Yellow))
+            } else {
+                Span::raw("  ")
+            };
+            match row {
+                StatusRow::Header { side, .. } => {
+                    let name = match side {
+                        StatusSide::Staged => "Staged",
+                        StatusSide::Unstaged => "Unstaged/Untracked",
+                    };
+                    ListItem::new(Span::styled(
+                        format!("{name} ({})", files.len()),
+                        Style::default()
+                            .fg(Color::White)
+                            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                    ))
+                }
+                StatusRow::Dir {
+                    path,
+                    depth,
+                    collapsed,
+                    ..
+                } => {
+                    let arrow = if *collapsed { "▸" } else { "▾" };
+                    ListItem::new(Spans::from(vec![
+                        marker,
+                        Span::raw("  ".repeat(*depth)),
+                        Span::styled(
+                            format!("{arrow} {}/", file_name(path)),
+                            Style::default()
+                                .fg(Color::Blue)
+                                .add_modifier(Modifier::BOLD),
+                        ),
+                        Span::styled(
+                            format!(" {}", code_counts(files)),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    ]))
+                }
+                StatusRow::File { index, depth } => {
+                    let s = &app.status_files[*index];
+                    let name = if app.status_tree {
+                        file_name(&s.path)
+                    } else {
+                        s.path.as_str()
+                    };
+                    ListItem::new(Spans::from(vec![
+                        marker,
+                        Span::raw("  ".repeat(*depth)),
+                        Span::styled(s.code().to_string(), status_style(s)),
+                        Span::raw(" "),
+                        Span::raw(name),
+                    ]))
+                }
+            }
         })
         .collect();
     let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Working Directory")
+                .title(status_title(app))
                 .border_style(border_style),
         )
         .highlight_style(
@@ -114,44 +315,177 @@ fn draw_status_panel<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
                 .bg(highlight_bg)
                 .add_modifier(Modifier::BOLD),
         );
-    f.render_stateful_widget(list, area, &mut app.status_list_state);
-}
-fn get_status_style(status: Status) -> (&'static str, Style) {
-    if status.is_wt_new() {
-        ("A ", Style::default().fg(Color::Green))
-    } else if status.is_wt_modified() {
-        ("M ", Style::default().fg(Color::Yellow))
-    } else if status.is_wt_deleted() {
-        ("D ", Style::default().fg(Color::Red))
-    } else if status.is_wt_renamed() {
-        ("R ", Style::default().fg(Color::Cyan))
-    } else if status.is_index_new() {
-        ("[A]", Style::default().fg(Color::Green))
-    } else if status.is_index_modified() {
-        ("[M]", Style::default().fg(Color::Yellow))
-    } else if status.is_index_deleted() {
-        ("[D]", Style::default().fg(Color::Red))
-    } else if status.is_index_renamed() {
-        ("[R]", Style::default().fg(Color::Cyan))
-    } else {
-        ("? ", Style::default().fg(Color::DarkGray))
+    let mut state = window_state(selected, app.status_list_offset);
+    f.render_stateful_widget(list, area, &mut state);
+}
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+/// How many files below a directory have each status letter, e.g. "3M 1?".
+fn code_counts(files: &[StatusInfo]) -> String {
+    let mut counts = std::collections::BTreeMap::new();
+    for file in files {
+        *counts.entry(file.code()).or_insert(0) += 1;
     }
+    counts
+        .iter()
+        .map(|(code, count)| format!("{count}{code}"))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
-fn draw_help<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
-    let help_text = match app.active_panel {
-        ActivePanel::Commits => Text::from("↓↑: Navigate | <Tab>: Switch | <P>: Push | q: Quit"),
-        ActivePanel::Status => Text::from(
-            "↓↑: Navigate | <Space>: Stage/Unstage | <c>: Commit | <Tab>: Switch | q: Quit",
-        ),
+fn status_title(app: &App) -> String {
+    match app.status_marks.len() {
+        0 => "Working Directory".to_string(),
+        marked => format!("Working Directory ({marked} marked)"),
+    }
+}
+fn status_style(file: &StatusInfo) -> Style {
+    let color = match file.code() {
+        'A' | '?' => Color::Green,
+        'D' => Color::Red,
+        'R' => Color::Cyan,
+        'U' => Color::Magenta,
+        _ => Color::Yellow,
     };
-    let help =
-        Paragraph::new(help_text).block(Block::default().borders(Borders::ALL).title("Help"));
+    Style::default().fg(color)
+}
+fn help_text(app: &App) -> &'static str {
+    match app.active_panel {
+        ActivePanel::Commits if app.commit_files.is_some() => {
+            "↓↑: Navigate | <Enter>: Open diff | <b>: Blame | <h>: History | <Esc>: Back to log"
+        }
+        ActivePanel::Commits => {
+            "↓↑: Navigate | <Enter>: Files | <m>: Mark | <p>: Merge parent | <d>: Diff revs | <w>/<i>: vs Worktree/Index | <t>: Tag | <Tab>/<S-Tab>: Switch | <P>: Push | <R>: Repos | <L>: Messages | q: Quit"
+        }
+        ActivePanel::Status => {
+            "↓↑: Navigate | <Space>: Stage/Unstage | <m>: Mark | <a>/<u>: Stage/Unstage all | <x>/<X>: Discard unstaged/all | <i>: Ignore | <t>: Tree | <Enter>/←→: Fold | <c>: Commit | <Tab>: Switch | q: Quit"
+        }
+        ActivePanel::Diff => {
+            "↓↑/PgUp/PgDn/g/G: Scroll | n/N: Hunk | ]/[: File | ←→: Pan | w: Wrap | s: Split | W: Whitespace | +/-: Context | a: Algorithm | r: Renames | Esc: Back"
+        }
+    }
+}
+/// Packs the " | "-separated entries of `text` into lines of at most `width`
+/// columns, breaking inside an entry only when it is wider than a line.
+fn help_lines(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for entry in text.split(" | ") {
+        let joined = if line.is_empty() {
+            entry.width()
+        } else {
+            line.width() + 3 + entry.width()
+        };
+        if joined <= width {
+            if !line.is_empty() {
+                line.push_str(" | ");
+            }
+            line.push_str(entry);
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in entry.chars() {
+            if line.width() + c.width().unwrap_or(0) > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+    lines.push(line);
+    lines
+}
+fn draw_help<B: Backend>(f: &mut Frame<B>, lines: Vec<String>, area: Rect) {
+    let text: Vec<Spans> = lines.into_iter().map(Spans::from).collect();
+    let help = Paragraph::new(text).block(Block::default().borders(Borders::ALL).title("Help"));
     f.render_widget(help, area);
 }
+/// "Diff", optionally naming the compared revisions, or "Blame <path>" /
+/// "History <path>" for a file of a commit.
+fn diff_panel_name(app: &App) -> String {
+    if let Some((title, _)) = &app.pinned_diff {
+        return format!("Diff {title}");
+    }
+    if app.diff_source == ActivePanel::Commits
+        && app.commit_files.is_none()
+        && let Some(marked) = app.marked_commit
+        && let Some(selected) = app
+            .commit_list_state
+            .selected()
+            .and_then(|i| app.commits.get(i))
+        && selected.id != marked.to_string()
+    {
+        return format!("Diff {:.7}..{:.7}", marked.to_string(), selected.id);
+    }
+    if app.diff_source == ActivePanel::Commits && app.commit_files.is_none() {
+        match app.merge_view {
+            MergeView::Parent(0) => {}
+            MergeView::Parent(parent) => return format!("Diff vs parent {}", parent + 1),
+            MergeView::Combined => return "Diff combined".to_string(),
+        }
+    }
+    let Some(files) = app
+        .commit_files
+        .as_ref()
+        .filter(|_| app.diff_source == ActivePanel::Commits)
+    else {
+        return "Diff".to_string();
+    };
+    match (files.view, files.selected_file()) {
+        (FileView::Blame, Some(file)) => format!("Blame {}", file.path),
+        (FileView::History, Some(file)) => format!("History {}", file.path),
+        _ => "Diff".to_string(),
+    }
+}
 fn draw_diff_panel<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
-    let diff_paragraph = Paragraph::new(app.diff_text.clone())
-        .block(Block::default().borders(Borders::ALL).title("Diff"));
-    f.render_widget(diff_paragraph, area);
+    let is_active = matches!(app.active_panel, ActivePanel::Diff);
+    let border_style = if is_active {
+        Style::default().fg(Color::White)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    };
+    let height = area.height.saturating_sub(2) as usize;
+    let width = area.width.saturating_sub(2) as usize;
+    app.diff_view_height = height;
+    app.diff_area = area;
+    let rows = &app.diff_rows;
+    let (side_by_side, hscroll) = match app.diff_layout {
+        DiffLayout::SideBySide => (true, app.diff_hscroll as usize),
+        DiffLayout::Unified => (false, 0),
+    };
+    let last = (app.diff_scroll + height).min(rows.len());
+    let visible: Vec<Spans> = (app.diff_scroll.min(last)..last)
+        .map(|i| rows.render_row(&app.diff_text, i, width, hscroll))
+        .collect();
+    let settings = app.diff_settings.describe();
+    let name = diff_panel_name(app);
+    let title = if app.diff_loading {
+        format!("{name} (loading...) {settings}")
+    } else if rows.is_empty() {
+        format!("{name} {settings}")
+    } else {
+        let layout = if side_by_side { " side-by-side" } else { "" };
+        let wrap = if app.wraps_diff() { " wrap" } else { "" };
+        format!(
+            "{name} [{}-{last}/{}]{layout}{wrap} · {settings}",
+            app.diff_scroll + 1,
+            rows.len()
+        )
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(border_style);
+    let diff_paragraph = if app.wraps_diff() {
+        Paragraph::new(visible).wrap(Wrap { trim: false })
+    } else if side_by_side {
+        Paragraph::new(visible)
+    } else {
+        Paragraph::new(visible).scroll((0, app.diff_hscroll))
+    };
+    f.render_widget(diff_paragraph.block(block), area);
 }
 fn centered_rect(percent_x: u16, height: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
@@ -197,3 +531,320 @@ fn draw_push_popup<B: Backend>(f: &mut Frame<B>, msg: &str) {
     f.render_widget(Clear, area);
     f.render_widget(text, area);
 }
+fn draw_confirm_popup<B: Backend>(f: &mut Frame<B>, confirmation: &Confirmation) {
+    let mut lines: Vec<Spans> = confirmation
+        .details
+        .iter()
+        .map(|line| Spans::from(line.as_str()))
+        .collect();
+    lines.push(Spans::default());
+    lines.push(Spans::from(vec![
+        Span::styled(
+            "y",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(": Confirm  "),
+        Span::styled("n/Esc", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": Cancel"),
+    ]));
+    lines.push(Spans::from(Span::styled(
+        format!(
+            "git config termigit.confirm.{} false skips this prompt",
+            confirmation.action.config_name()
+        ),
+        Style::default().fg(Color::DarkGray),
+    )));
+    let height = (lines.len() as u16 + 2).min(f.size().height);
+    let area = centered_rect(60, height, f.size());
+    let text = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(Span::styled(
+                confirmation.title.as_str(),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(text, area);
+}
+fn draw_tag_popup<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let area = centered_rect(60, 3, f.size());
+    let input = Paragraph::new(app.tag_input.value()).style(Style::default().fg(Color::White));
+    let block = Block::default()
+        .title("Tag: <name> [message] (Enter to submit, Esc to cancel)")
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(input.block(block), area);
+
+    f.set_cursor(
+        area.x + app.tag_input.visual_cursor() as u16 + 1,
+        area.y + 1,
+    );
+}
+fn draw_revspec_popup<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let area = centered_rect(60, 3, f.size());
+    let input = Paragraph::new(app.revspec_input.value()).style(Style::default().fg(Color::White));
+    let block = Block::default()
+        .title("Diff revision, e.g. HEAD~3 or main..feature (Enter to submit, Esc to cancel)")
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    f.render_widget(input.block(block), area);
+
+    f.set_cursor(
+        area.x + app.revspec_input.visual_cursor() as u16 + 1,
+        area.y + 1,
+    );
+}
+fn draw_repo_switcher<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let height = (app.repo_summaries.len() as u16 + 2).min(f.size().height);
+    let area = centered_rect(70, height, f.size());
+    let current = app.repo.workdir().unwrap_or(app.repo.path()).to_path_buf();
+    let name_width = app
+        .repo_summaries
+        .iter()
+        .map(|summary| summary.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<ListItem> = app
+        .repo_summaries
+        .iter()
+        .map(|summary| {
+            let marker = if summary.path == current { "* " } else { "  " };
+            let mut spans = vec![
+                Span::raw(marker),
+                Span::styled(
+                    format!("{:name_width$}  ", summary.name),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+            ];
+            if !summary.loaded {
+                spans.push(Span::styled(
+                    "loading...",
+                    Style::default().fg(Color::DarkGray),
+                ));
+                return ListItem::new(Spans::from(spans));
+            }
+            spans.push(Span::styled(
+                head_label(&summary.branch),
+                Style::default().fg(Color::Cyan),
+            ));
+            if summary.dirty {
+                spans.push(Span::styled(" *", Style::default().fg(Color::Red)));
+            }
+            spans.extend(divergence_spans(&summary.branch));
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Repositories (Enter to switch, Esc to close)")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        );
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut app.repo_switcher_state);
+}
+/// The branch name, or the short id of a detached HEAD.
+fn head_label(branch: &BranchStatus) -> String {
+    match (&branch.branch, &branch.head) {
+        (Some(name), _) => name.clone(),
+        (None, Some(id)) => format!("({id})"),
+        (None, None) => "(no commits)".to_string(),
+    }
+}
+
+fn divergence_spans(branch: &BranchStatus) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    if branch.ahead > 0 {
+        spans.push(Span::styled(
+            format!(" ↑{}", branch.ahead),
+            Style::default().fg(Color::Green),
+        ));
+    }
+    if branch.behind > 0 {
+        spans.push(Span::styled(
+            format!(" ↓{}", branch.behind),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    spans
+}
+
+/// How `git status` names an operation left in progress.
+fn state_label(state: RepositoryState) -> Option<&'static str> {
+    Some(match state {
+        RepositoryState::Clean => return None,
+        RepositoryState::Merge => "MERGING",
+        RepositoryState::Revert | RepositoryState::RevertSequence => "REVERTING",
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => "CHERRY-PICKING",
+        RepositoryState::Bisect => "BISECTING",
+        RepositoryState::Rebase
+        | RepositoryState::RebaseInteractive
+        | RepositoryState::RebaseMerge => "REBASING",
+        RepositoryState::ApplyMailbox => "AM",
+        RepositoryState::ApplyMailboxOrRebase => "AM/REBASE",
+    })
+}
+
+fn draw_status_bar<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let branch = &app.branch_status;
+    let head = match (&branch.branch, &branch.head) {
+        (None, Some(id)) => Span::styled(
+            format!("HEAD detached at {id}"),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
+        _ => Span::styled(
+            head_label(branch),
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ),
+    };
+    let mut spans = vec![Span::raw(" "), head];
+    if let Some(upstream) = &branch.upstream {
+        spans.push(Span::styled(
+            format!(" → {upstream}"),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    spans.extend(divergence_spans(branch));
+    if let Some(state) = state_label(app.repo_state) {
+        spans.push(Span::raw(" │ "));
+        spans.push(Span::styled(
+            state,
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(latest) = app.notifications.latest() {
+        let (_, color) = severity_style(latest.severity);
+        spans.push(Span::raw(" │ "));
+        spans.push(Span::styled(
+            latest.message.as_str(),
+            Style::default().fg(color),
+        ));
+    }
+    f.render_widget(Paragraph::new(Spans::from(spans)), area);
+}
+
+fn severity_style(severity: Severity) -> (&'static str, Color) {
+    match severity {
+        Severity::Info => ("info", Color::Reset),
+        Severity::Success => ("ok", Color::Green),
+        Severity::Warning => ("warning", Color::Yellow),
+        Severity::Error => ("error", Color::Red),
+    }
+}
+
+/// Stacks the live toasts in the top right corner, newest on top.
+fn draw_toasts<B: Backend>(f: &mut Frame<B>, app: &App) {
+    let screen = f.size();
+    let width = screen.width.min(60);
+    let inner = width.saturating_sub(2).max(1) as usize;
+    let mut y = screen.y + 1;
+    for toast in app.notifications.toasts() {
+        let lines = toast.message.chars().count().div_ceil(inner).clamp(1, 4) as u16;
+        let height = lines + 2;
+        if y + height > screen.bottom() {
+            break;
+        }
+        let area = Rect::new(screen.right() - width, y, width, height);
+        let (label, color) = severity_style(toast.severity);
+        let text = Paragraph::new(toast.message.as_str())
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .title(Span::styled(
+                        label,
+                        Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    ))
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(color)),
+            );
+        f.render_widget(Clear, area);
+        f.render_widget(text, area);
+        y += height;
+    }
+}
+
+fn draw_message_log<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let height = (app.notifications.len() as u16 + 2)
+        .max(3)
+        .min(f.size().height.saturating_sub(2));
+    let area = centered_rect(80, height, f.size());
+    let items: Vec<ListItem> = app
+        .notifications
+        .log()
+        .map(|n| {
+            let (label, color) = severity_style(n.severity);
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("{label:7} "),
+                    Style::default().fg(color).add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!("{:>4} ago  ", format_age(n.created.elapsed().as_secs())),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(n.message.as_str()),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Messages (Esc to close)")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        );
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut app.message_log_state);
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        _ => format!("{}h", secs / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_lines_fit_the_width_and_keep_every_entry() {
+        let text = "↓↑: Navigate | <Space>: Stage/Unstage | <m>: Mark | q: Quit";
+        let lines = help_lines(text, 24);
+        assert_eq!(
+            lines,
+            [
+                "↓↑: Navigate",
+                "<Space>: Stage/Unstage",
+                "<m>: Mark | q: Quit"
+            ]
+        );
+        assert!(lines.iter().all(|line| line.width() <= 24));
+    }
+
+    #[test]
+    fn help_lines_break_entries_wider_than_a_line() {
+        assert_eq!(
+            help_lines("<Enter>: Open | q", 6),
+            ["<Enter", ">: Ope", "n | q"]
+        );
+    }
+}
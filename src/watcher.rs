@@ -0,0 +1,260 @@
+// src/watcher.rs
+
+use git2::Repository;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// The directories under `.git` whose contents matter, watched recursively.
+/// The rest of `.git`, `objects/` and `logs/` in particular, only has its
+/// top level watched, which holds HEAD, the index and `packed-refs`.
+const GIT_SUBDIRS: [&str; 3] = ["refs", "rebase-merge", "rebase-apply"];
+const DEBOUNCE: Duration = Duration::from_millis(250);
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// A debounced batch of filesystem changes relevant to the repository.
+#[derive(Default)]
+pub struct RepoChange {
+    /// HEAD, a ref or `packed-refs` moved or an operation such as a merge
+    /// started or ended, so the log and status bar may be stale.
+    pub refs_changed: bool,
+    pub index_changed: bool,
+    /// Changed paths in the working tree, relative to its root.
+    pub worktree_paths: HashSet<PathBuf>,
+}
+
+enum RawChange {
+    Refs,
+    Index,
+    WorkTree(PathBuf),
+}
+
+/// Watches the working tree and the `.git` directory, delivering debounced
+/// `RepoChange` batches on `receiver`. Ignored directories such as `target/`
+/// or `node_modules/` are left out, so builds neither flood the channel nor
+/// use up the system's watch limit.
+pub struct RepoWatcher {
+    watcher: RecommendedWatcher,
+    /// Answers the ignore rules; `None` for a bare repository.
+    repo: Option<Repository>,
+    git_dir: PathBuf,
+    /// The work tree directories being watched, each on its own.
+    watched: HashSet<PathBuf>,
+    /// The `GIT_SUBDIRS` that exist and are being watched.
+    git_subdirs: HashSet<&'static str>,
+    pub receiver: mpsc::UnboundedReceiver<RepoChange>,
+}
+
+impl RepoWatcher {
+    pub fn new(workdir: Option<&Path>, git_dir: &Path) -> notify::Result<Self> {
+        let (raw_tx, raw_rx) = mpsc::unbounded_channel();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let git_dir_owned = git_dir.to_path_buf();
+        let workdir_owned = workdir.map(Path::to_path_buf);
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                let Ok(event) = res else { return };
+                if event.kind.is_access() {
+                    return;
+                }
+                for path in event.paths {
+                    if let Some(change) = classify(&path, workdir_owned.as_deref(), &git_dir_owned)
+                    {
+                        let _ = raw_tx.send(change);
+                    }
+                }
+            })?;
+
+        watcher.watch(git_dir, RecursiveMode::NonRecursive)?;
+        let repo =
+            match workdir {
+                Some(_) => Some(Repository::open(git_dir).map_err(|e| {
+                    notify::Error::generic(&format!("Cannot read ignore rules: {e}"))
+                })?),
+                None => None,
+            };
+        let mut this = Self {
+            watcher,
+            repo,
+            git_dir: git_dir.to_path_buf(),
+            watched: HashSet::new(),
+            git_subdirs: HashSet::new(),
+            receiver: rx,
+        };
+        this.watch_git_subdirs()?;
+        if let Some(workdir) = workdir {
+            this.watch_tree(workdir)?;
+        }
+
+        tokio::spawn(debounce(raw_rx, tx));
+
+        Ok(this)
+    }
+
+    /// Follows the directory structure after `change`: watches directories
+    /// that were created, and after a `.gitignore` edit any that are no
+    /// longer ignored.
+    pub fn watch_new_dirs(&mut self, change: &RepoChange) -> notify::Result<()> {
+        if change.refs_changed {
+            // A rebase may have started or ended.
+            self.watch_git_subdirs()?;
+        }
+        let Some(workdir) = self
+            .repo
+            .as_ref()
+            .and_then(|repo| repo.workdir())
+            .map(Path::to_path_buf)
+        else {
+            return Ok(());
+        };
+        let rules_changed = change
+            .worktree_paths
+            .iter()
+            .any(|path| path.file_name().is_some_and(|name| name == ".gitignore"));
+        if rules_changed {
+            return self.watch_tree(&workdir);
+        }
+        for path in &change.worktree_paths {
+            let dir = workdir.join(path);
+            if !dir.is_dir() {
+                // Removed, and with it the watches on it and below it.
+                self.watched.retain(|watched| !watched.starts_with(&dir));
+            } else if !self.watched.contains(&dir) {
+                self.watch_tree(&dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Watches those of `GIT_SUBDIRS` that exist and are not watched yet.
+    fn watch_git_subdirs(&mut self) -> notify::Result<()> {
+        for name in GIT_SUBDIRS {
+            let dir = self.git_dir.join(name);
+            if !dir.is_dir() {
+                self.git_subdirs.remove(name);
+            } else if !self.git_subdirs.contains(name) {
+                match self.watcher.watch(&dir, RecursiveMode::Recursive) {
+                    Ok(()) => {
+                        self.git_subdirs.insert(name);
+                    }
+                    Err(notify::Error {
+                        kind: notify::ErrorKind::PathNotFound,
+                        ..
+                    }) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Watches `root` and the directories below it, skipping ignored ones
+    /// and any `.git` directory.
+    fn watch_tree(&mut self, root: &Path) -> notify::Result<()> {
+        let Some(repo) = &self.repo else {
+            return Ok(());
+        };
+        let workdir = repo.workdir().unwrap_or(repo.path());
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            if dir == self.git_dir || dir.file_name().is_some_and(|name| name == ".git") {
+                continue;
+            }
+            if let Ok(relative) = dir.strip_prefix(workdir)
+                && !relative.as_os_str().is_empty()
+                && repo.status_should_ignore(relative).unwrap_or(false)
+            {
+                continue;
+            }
+            if !self.watched.contains(&dir) {
+                match self.watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                    Ok(()) => {
+                        self.watched.insert(dir.clone());
+                    }
+                    // Removed while we were getting to it.
+                    Err(notify::Error {
+                        kind: notify::ErrorKind::PathNotFound,
+                        ..
+                    }) => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            // `file_type` does not follow symlinks, so linked directories
+            // are not entered.
+            pending.extend(
+                entries
+                    .flatten()
+                    .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+                    .map(|entry| entry.path()),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn classify(path: &Path, workdir: Option<&Path>, git_dir: &Path) -> Option<RawChange> {
+    if let Ok(relative) = path.strip_prefix(git_dir) {
+        let name = relative.to_string_lossy();
+        if name.ends_with(".lock") {
+            return None;
+        }
+        return match name.as_ref() {
+            "index" => Some(RawChange::Index),
+            "HEAD" | "packed-refs" | "MERGE_HEAD" | "ORIG_HEAD" | "CHERRY_PICK_HEAD"
+            | "REVERT_HEAD" | "BISECT_LOG" => Some(RawChange::Refs),
+            _ if ["refs", "rebase-merge", "rebase-apply"]
+                .iter()
+                .any(|dir| relative.starts_with(dir)) =>
+            {
+                Some(RawChange::Refs)
+            }
+            _ => None,
+        };
+    }
+    let relative = path.strip_prefix(workdir?).ok()?;
+    if relative.as_os_str().is_empty() {
+        return None;
+    }
+    Some(RawChange::WorkTree(relative.to_path_buf()))
+}
+
+async fn debounce(
+    mut raw_rx: mpsc::UnboundedReceiver<RawChange>,
+    tx: mpsc::UnboundedSender<RepoChange>,
+) {
+    while let Some(first) = raw_rx.recv().await {
+        let mut batch = RepoChange::default();
+        batch.add(first);
+        let give_up = Instant::now() + MAX_DELAY;
+        loop {
+            let deadline = (Instant::now() + DEBOUNCE).min(give_up);
+            match tokio::time::timeout_at(deadline, raw_rx.recv()).await {
+                Ok(Some(change)) => batch.add(change),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        if tx.send(batch).is_err() {
+            return;
+        }
+    }
+}
+
+impl RepoChange {
+    fn add(&mut self, change: RawChange) {
+        match change {
+            RawChange::Refs => self.refs_changed = true,
+            RawChange::Index => self.index_changed = true,
+            RawChange::WorkTree(path) => {
+                self.worktree_paths.insert(path);
+            }
+        }
+    }
+}
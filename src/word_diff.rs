@@ -0,0 +1,211 @@
+// src/word_diff.rs
+
+use crate::types::diff_info::DiffInfo;
+use similar::{Algorithm, DiffTag, capture_diff_slices};
+use std::ops::Range;
+
+/// Pairs sharing less than this fraction of their text count as rewritten
+/// rather than edited, and get no intra-line emphasis.
+const MIN_SIMILARITY: f32 = 0.3;
+/// Very long lines (minified files and the like) are not worth tokenizing.
+const MAX_LINE_LEN: usize = 2000;
+
+/// Fills in `DiffLine::emphasis` for every removed line that is paired with an
+/// added line, marking the words that actually differ between the two.
+pub fn mark_changes(info: &mut DiffInfo) {
+    for hunk in info.files.iter_mut().flat_map(|file| file.hunks.iter_mut()) {
+        for (old, new) in hunk.paired_lines() {
+            let (Some(old), Some(new)) = (old, new) else {
+                continue;
+            };
+            if old == new {
+                continue;
+            }
+            if let Some((old_ranges, new_ranges)) =
+                changed_ranges(&hunk.lines[old].content, &hunk.lines[new].content)
+            {
+                hunk.lines[old].emphasis = old_ranges;
+                hunk.lines[new].emphasis = new_ranges;
+            }
+        }
+    }
+}
+
+type Ranges = Vec<Range<usize>>;
+
+fn changed_ranges(old: &str, new: &str) -> Option<(Ranges, Ranges)> {
+    if old.len() > MAX_LINE_LEN || new.len() > MAX_LINE_LEN {
+        return None;
+    }
+    let old_tokens = tokenize(old);
+    let new_tokens = tokenize(new);
+    let old_words: Vec<&str> = old_tokens.iter().map(|r| &old[r.clone()]).collect();
+    let new_words: Vec<&str> = new_tokens.iter().map(|r| &new[r.clone()]).collect();
+
+    let mut unchanged = 0;
+    let mut old_ranges = Vec::new();
+    let mut new_ranges = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_words, &new_words) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            unchanged += old_tokens[old_range].iter().map(|r| r.len()).sum::<usize>();
+            continue;
+        }
+        push_span(&mut old_ranges, &old_tokens, old_range);
+        push_span(&mut new_ranges, &new_tokens, new_range);
+    }
+
+    let total = old.len() + new.len();
+    if total == 0 || (2 * unchanged) as f32 / (total as f32) < MIN_SIMILARITY {
+        return None;
+    }
+    Some((old_ranges, new_ranges))
+}
+
+/// Adds the byte range covered by `tokens[range]`, merging it into the
+/// previous range when they touch.
+fn push_span(ranges: &mut Ranges, tokens: &[Range<usize>], range: Range<usize>) {
+    if range.is_empty() {
+        return;
+    }
+    let span = tokens[range.start].start..tokens[range.end - 1].end;
+    match ranges.last_mut() {
+        Some(last) if last.end == span.start => last.end = span.end,
+        _ => ranges.push(span),
+    }
+}
+
+/// Splits a line into runs of word characters, runs of whitespace and single
+/// punctuation characters, as byte ranges.
+fn tokenize(line: &str) -> Vec<Range<usize>> {
+    #[derive(PartialEq)]
+    enum Class {
+        Word,
+        Space,
+        Other,
+    }
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            Class::Word
+        } else if c.is_whitespace() {
+            Class::Space
+        } else {
+            Class::Other
+        }
+    };
+
+    let mut tokens: Vec<Range<usize>> = Vec::new();
+    let mut previous = None;
+    for (start, c) in line.char_indices() {
+        let current = class(c);
+        let end = start + c.len_utf8();
+        match tokens.last_mut() {
+            Some(last) if previous.as_ref() == Some(&current) && current != Class::Other => {
+                last.end = end;
+            }
+            _ => tokens.push(start..end),
+        }
+        previous = Some(current);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::diff_info::{DiffHunk, DiffLine, DiffLineKind, FileDiff};
+
+    /// Runs `mark_changes` over one hunk written as `git diff` lines, e.g.
+    /// `["-old", "+new", " context"]`, and returns each line's emphasized
+    /// text.
+    fn emphasized(lines: &[&str]) -> Vec<Vec<String>> {
+        let lines = lines
+            .iter()
+            .map(|line| {
+                let (origin, content) = line.split_at(1);
+                DiffLine {
+                    kind: match origin {
+                        "-" => DiffLineKind::Removed,
+                        "+" => DiffLineKind::Added,
+                        _ => DiffLineKind::Context,
+                    },
+                    old_lineno: None,
+                    new_lineno: None,
+                    content: content.to_string(),
+                    emphasis: Vec::new(),
+                    syntax: Vec::new(),
+                    markers: None,
+                }
+            })
+            .collect();
+        let mut info = DiffInfo {
+            files: vec![FileDiff {
+                path: "f".to_string(),
+                status: 'M',
+                header: Vec::new(),
+                hunks: vec![DiffHunk {
+                    header: "@@ -1 +1 @@".to_string(),
+                    lines,
+                }],
+            }],
+            ..DiffInfo::default()
+        };
+        mark_changes(&mut info);
+        info.files[0].hunks[0]
+            .lines
+            .iter()
+            .map(|line| {
+                line.emphasis
+                    .iter()
+                    .map(|range| line.content[range.clone()].to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn paired_lines_emphasize_the_changed_words() {
+        assert_eq!(
+            emphasized(&["-let x = 1;", "+let x = 2;"]),
+            [vec!["1"], vec!["2"]]
+        );
+        assert_eq!(
+            emphasized(&["-a.b.c", "+a,b,c"]),
+            [vec![".", "."], vec![",", ","]]
+        );
+    }
+
+    #[test]
+    fn unpaired_lines_are_left_plain() {
+        assert_eq!(
+            emphasized(&["-keep this", "-dropped line", "+keep that"]),
+            [vec!["this"], vec![], vec!["that"]]
+        );
+        // Context between the runs keeps them from pairing.
+        assert_eq!(
+            emphasized(&["-value one", " context", "+value two"]),
+            [Vec::<&str>::new(), vec![], vec![]]
+        );
+    }
+
+    #[test]
+    fn rewritten_lines_are_left_plain() {
+        assert_eq!(
+            emphasized(&["-alpha beta", "+gamma delta"]),
+            [Vec::<&str>::new(), vec![]]
+        );
+    }
+
+    #[test]
+    fn multibyte_text_is_split_on_char_boundaries() {
+        assert_eq!(
+            emphasized(&["-café au lait", "+café au thé"]),
+            [vec!["lait"], vec!["thé"]]
+        );
+        assert_eq!(
+            emphasized(&["-日本 語 です", "+日本 人 です"]),
+            [vec!["語"], vec!["人"]]
+        );
+        assert_eq!(emphasized(&["-x → y", "+x ⇒ y"]), [vec!["→"], vec!["⇒"]]);
+    }
+}